@@ -2,40 +2,152 @@
 use syn::*;
 use synstructure::*;
 
-pub fn accessors(s: &Structure, gcs: &[&BindingInfo]) -> TokenStream {
+pub fn accessors(s: &Structure) -> Result<TokenStream> {
     let s_ast: &DeriveInput = s.ast();
+    let is_enum = matches!(s_ast.data, Data::Enum(_));
 
-    // Collector of bindings with `#[gc]` attributes
+    let accessors: TokenStream = s
+        .variants()
+        .iter()
+        .flat_map(|v| {
+            v.bindings()
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| super::is_tagged(b))
+                .map(move |(idx, b)| {
+                    if is_enum {
+                        variant_accessor(s_ast, v, idx, b)
+                    } else {
+                        struct_accessor(s_ast, idx, b)
+                    }
+                })
+        })
+        .collect::<Result<_>>()?;
 
-    // TODO properly handle enum and tuple structs
-    if gcs.len() > 0 && s.variants().len() != 1 {
-        panic!("gc_accessor attributes can only be used on structs");
+    // inherent impl with all the accessors
+    let (impl_generics, ty_generics, where_clauses) = s_ast.generics.split_for_impl();
+    let name = &s_ast.ident;
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clauses {
+            #accessors
+        }
+    })
+}
+
+fn struct_accessor(s_ast: &DeriveInput, idx: usize, b: &BindingInfo) -> Result<TokenStream> {
+    let b_ast: &Field = b.ast();
+
+    let field = member(b_ast, idx);
+    let visibility: &Visibility = &s_ast.vis;
+    let method = method_name(b, idx, None)?;
+
+    let ty: &Type = &b_ast.ty;
+
+    if super::field_has_attr(b, "project") {
+        return Ok(quote! {
+            #visibility fn #method<'__root>(self: nocturne::Gc<'__root, Self>) -> nocturne::GcRef<'__root, #ty> {
+                nocturne::Gc::map(self, |this| &this.#field)
+            }
+        });
     }
 
-    let accessors: TokenStream = gcs.iter().map(|b| {
-        let b_ast: &Field = b.ast();
+    Ok(quote! {
+        #visibility fn #method<'__root>(self: &'__root nocturne::Gc<'__root, Self>) -> <#ty as nocturne::raw::Store<'__root>>::Accessor {
+            unsafe {
+                nocturne::raw::Store::rooted(&self.#field)
+            }
+        }
+    })
+}
+
+/// An accessor returning `None` unless the object is the variant of the field
+fn variant_accessor(
+    s_ast: &DeriveInput,
+    v: &VariantInfo,
+    idx: usize,
+    b: &BindingInfo,
+) -> Result<TokenStream> {
+    let b_ast: &Field = b.ast();
 
-        let field: &Ident = b_ast.ident.as_ref().unwrap();
-        let visibility: &Visibility = &s_ast.vis;
-        let method: &Ident = b_ast.ident.as_ref().unwrap();
+    let field = member(b_ast, idx);
+    let variant: &Ident = v.ast().ident;
+    let visibility: &Visibility = &s_ast.vis;
+    let method = method_name(b, idx, Some(variant))?;
 
-        let ty: &Type = &b_ast.ty;
+    let ty: &Type = &b_ast.ty;
 
-        quote! {
-            #visibility fn #method<'__root>(self: &'__root nocturne::Gc<'__root, Self>) -> <#ty as nocturne::raw::Store<'__root>>::Accessor {
-                unsafe {
-                    nocturne::raw::Store::rooted(&self.#field)
+    if super::field_has_attr(b, "project") {
+        return Ok(quote! {
+            #[allow(unreachable_patterns)]
+            #visibility fn #method<'__root>(self: nocturne::Gc<'__root, Self>) -> Option<nocturne::GcRef<'__root, #ty>> {
+                match &*self {
+                    Self::#variant { .. } => Some(nocturne::Gc::map(self, |this| match this {
+                        Self::#variant { #field: field, .. } => field,
+                        _ => unreachable!(),
+                    })),
+                    _ => None,
                 }
             }
+        });
+    }
+
+    Ok(quote! {
+        #[allow(unreachable_patterns)]
+        #visibility fn #method<'__root>(self: &'__root nocturne::Gc<'__root, Self>) -> Option<<#ty as nocturne::raw::Store<'__root>>::Accessor> {
+            match &**self {
+                Self::#variant { #field: field, .. } => unsafe {
+                    Some(nocturne::raw::Store::rooted(field))
+                },
+                _ => None,
+            }
         }
-    }).collect();
+    })
+}
 
-    // inherent impl with all the accessors
-    let (impl_generics, ty_generics, where_clauses) = s_ast.generics.split_for_impl();
-    let name = &s_ast.ident;
-    quote! {
-        impl #impl_generics #name #ty_generics #where_clauses {
-            #accessors
+fn member(field: &Field, idx: usize) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(idx)),
+    }
+}
+
+/// The name given with `#[gc(name = "..")]`, or the field name prefixed with the variant's
+pub fn method_name(b: &BindingInfo, idx: usize, variant: Option<&Ident>) -> Result<Ident> {
+    if let Some(name) = super::field_attr_value(b, "name") {
+        return name.parse();
+    }
+
+    let field = match &b.ast().ident {
+        Some(ident) => ident.to_string(),
+        None => format!("field_{}", idx),
+    };
+    Ok(match variant {
+        Some(variant) => format_ident!("{}_{}", snake_case(&variant.to_string()), field),
+        None => format_ident!("{}", field),
+    })
+}
+
+/// Words start at a capital following a lowercase letter or digit, or at the last capital of a
+/// run followed by a lowercase letter, so that `HTTPServer` becomes `http_server`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                let prev = chars[i - 1];
+                let next_is_lowercase = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+                if prev.is_lowercase()
+                    || prev.is_numeric()
+                    || prev.is_uppercase() && next_is_lowercase
+                {
+                    snake.push('_');
+                }
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
         }
     }
+    snake
 }
@@ -12,8 +12,9 @@
 mod reroot;
 mod trace;
 
+use std::collections::HashSet;
+
 use proc_macro2::*;
-use syn::parse::Parser;
 use syn::punctuated::Punctuated;
 use syn::*;
 
@@ -25,36 +26,79 @@
 decl_derive!([GC, attributes(gc)] => gc_derive);
 
 fn gc_derive(s: synstructure::Structure) -> TokenStream {
-    let tagged_fields = tagged_fields(&s);
-    let accessors = accessors(&s, &tagged_fields[..]);
-    let trace_impl = trace_impl(&s);
-    let reroot_impl = reroot_impl(&s);
-    let null_trace_impl = null_trace_impl(&s);
-    let gc_impl = gc_impl(&s);
-    quote! {
+    try_gc_derive(&s).unwrap_or_else(|err| err.to_compile_error())
+}
+
+fn try_gc_derive(s: &synstructure::Structure) -> Result<TokenStream> {
+    check_attrs(s)?;
+    let accessors = accessors(s)?;
+    let trace_impl = trace_impl(s)?;
+    let reroot_impl = reroot_impl(s)?;
+    let null_trace_impl = null_trace_impl(s);
+    let gc_impl = gc_impl(s);
+    Ok(quote! {
         #accessors
         #trace_impl
         #reroot_impl
         #null_trace_impl
         #gc_impl
-    }
+    })
 }
 
 fn gc_impl(s: &synstructure::Structure) -> TokenStream {
+    let mut s = s.clone();
+    s.add_bounds(synstructure::AddBounds::None);
     s.gen_impl(quote! {
         extern crate nocturne;
 
-        gen impl<'__root> nocturne::GC<'__root> for @Self {
+        gen impl<'__root> nocturne::GC<'__root> for @Self where
+            Self: nocturne::raw::Reroot<'__root> + nocturne::raw::Trace,
+        {
         }
     })
 }
 
-fn tagged_fields<'a>(s: &'a synstructure::Structure<'a>) -> Vec<&'a synstructure::BindingInfo<'a>> {
-    s.variants()
-        .iter()
-        .flat_map(|v| v.bindings())
-        .filter(|binding| binding.ast().attrs.iter().any(|attr| is_attr(attr, "gc")))
-        .collect()
+/// Tell if the field has a `#[gc]` attribute
+fn is_tagged(binding: &synstructure::BindingInfo) -> bool {
+    binding.ast().attrs.iter().any(|attr| is_attr(attr, "gc"))
+}
+
+/// Tell if the field has `ident` among the arguments of its `#[gc(..)]` attribute
+fn field_has_attr(binding: &synstructure::BindingInfo, ident: &str) -> bool {
+    find_arg(&binding.ast().attrs, ident).is_some()
+}
+
+/// Get the value of `ident` in the `#[gc(ident = "..")]` attribute of the field
+fn field_attr_value(binding: &synstructure::BindingInfo, ident: &str) -> Option<LitStr> {
+    attr_value(&binding.ast().attrs, ident)
+}
+
+/// Get the where predicates given with `#[gc(bound = "..")]` on the type
+///
+/// They only replace the bounds of the `Trace` impl: `Reroot` needs each field to reroot to the
+/// type it names in `Rerooted`, which no other bound can stand for.
+fn type_bound(s: &synstructure::Structure) -> Result<Option<Vec<WherePredicate>>> {
+    match attr_value(&s.ast().attrs, "bound") {
+        Some(bound) => Ok(Some(parse_bound(&bound)?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_bound(bound: &LitStr) -> Result<Vec<WherePredicate>> {
+    let predicates = bound.parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+    Ok(predicates.into_iter().collect())
+}
+
+/// Find the value of `ident` among the arguments of the `#[gc(..)]` attributes
+fn attr_value(attrs: &[Attribute], ident: &str) -> Option<LitStr> {
+    gc_args(attrs).find_map(|meta| match meta {
+        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+            path,
+            lit: Lit::Str(value),
+            ..
+        })) if path.is_ident(ident) => Some(value),
+        _ => None,
+    })
 }
 
 fn is_attr(attr: &syn::Attribute, ident: &str) -> bool {
@@ -66,17 +110,114 @@ fn is_attr(attr: &syn::Attribute, ident: &str) -> bool {
 }
 
 fn has_attr(s: &synstructure::Structure, ident: &str) -> bool {
-    if let Some(attr) = s.ast().attrs.iter().find(|attr| is_attr(attr, "gc")) {
-        let attr_content = attr.tokens.clone().into_iter().next();
-        if let Some(TokenTree::Group(attr_content)) = attr_content {
-            let idents = Punctuated::<Ident, token::Comma>::parse_terminated
-                .parse2(attr_content.stream())
-                .unwrap();
-            idents.into_iter().any(|i| i == ident)
-        } else {
-            false
+    find_arg(&s.ast().attrs, ident).is_some()
+}
+
+/// Find `ident` among the arguments of the `#[gc(..)]` attributes
+fn find_arg(attrs: &[Attribute], ident: &str) -> Option<Path> {
+    gc_args(attrs).find_map(|meta| match meta {
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident(ident) => Some(path),
+        _ => None,
+    })
+}
+
+/// The arguments of every `#[gc(..)]` attribute, which `check_attrs` has validated
+fn gc_args(attrs: &[Attribute]) -> impl Iterator<Item = NestedMeta> + '_ {
+    attrs
+        .iter()
+        .filter(|attr| is_attr(attr, "gc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+}
+
+/// Check that every `#[gc(..)]` attribute is well-formed and only has known arguments
+fn check_attrs(s: &synstructure::Structure) -> Result<()> {
+    for attr in s.ast().attrs.iter().filter(|attr| is_attr(attr, "gc")) {
+        for arg in attr_list(attr)? {
+            match &arg {
+                NestedMeta::Meta(Meta::Path(path))
+                    if ["finalize", "unsafe_finalize", "null_trace"]
+                        .iter()
+                        .any(|ident| path.is_ident(ident)) => {}
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                    if path.is_ident("bound") =>
+                {
+                    match lit {
+                        Lit::Str(bound) => {
+                            parse_bound(bound)?;
+                        }
+                        _ => return Err(Error::new_spanned(lit, "expected a bound string")),
+                    }
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        arg,
+                        "expected `finalize`, `unsafe_finalize`, `null_trace` or `bound = \"..\"`",
+                    ))
+                }
+            }
+        }
+    }
+
+    let fields = s.variants().iter().flat_map(|v| v.bindings());
+    for attr in fields
+        .flat_map(|b| &b.ast().attrs)
+        .filter(|attr| is_attr(attr, "gc"))
+    {
+        for arg in attr_list(attr)? {
+            match &arg {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("project") => {}
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                    if path.is_ident("name") =>
+                {
+                    match lit {
+                        Lit::Str(name) => {
+                            name.parse::<Ident>().map_err(|_| {
+                                Error::new_spanned(name, "expected a valid method name")
+                            })?;
+                        }
+                        _ => return Err(Error::new_spanned(lit, "expected a method name string")),
+                    }
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        arg,
+                        "expected `project` or `name = \"..\"`",
+                    ))
+                }
+            }
+        }
+    }
+
+    // Accessor names must not collide, whether given or generated, even across variants
+    let is_enum = matches!(s.ast().data, Data::Enum(_));
+    let mut names = HashSet::new();
+    for v in s.variants() {
+        let variant = if is_enum { Some(v.ast().ident) } else { None };
+        for (idx, b) in v.bindings().iter().enumerate() {
+            if !is_tagged(b) {
+                continue;
+            }
+            if !names.insert(accessors::method_name(b, idx, variant)?.to_string()) {
+                return Err(match field_attr_value(b, "name") {
+                    Some(name) => Error::new_spanned(name, "duplicate method name"),
+                    None => Error::new_spanned(b.ast(), "duplicate method name"),
+                });
+            }
         }
-    } else {
-        false
+    }
+
+    Ok(())
+}
+
+/// The arguments of `#[gc(..)]`, which is empty for a bare `#[gc]`
+fn attr_list(attr: &Attribute) -> Result<Vec<NestedMeta>> {
+    match attr.parse_meta()? {
+        Meta::List(list) => Ok(list.nested.into_iter().collect()),
+        Meta::Path(_) => Ok(Vec::new()),
+        meta @ Meta::NameValue(_) => Err(Error::new_spanned(meta, "expected `#[gc(..)]`")),
     }
 }
@@ -2,41 +2,125 @@
 use syn::*;
 use synstructure::*;
 
-pub fn reroot_impl(s: &Structure) -> TokenStream {
-    let rerooted = rerooted(s);
+pub fn reroot_impl(s: &Structure) -> Result<TokenStream> {
+    let mut folder = RootFolder::new(s);
+    let rerooted = fold::fold_path_segment(&mut folder, self_type(s));
 
-    let bounds = bounds(s);
+    let param_bounds = param_bounds(s, &mut folder);
+    let where_bounds = where_bounds(s, &mut folder);
+    let bounds = bounds(s, &mut folder);
+    if let Some(err) = folder.error {
+        return Err(err);
+    }
 
-    s.gen_impl(quote! {
+    let mut s = s.clone();
+    s.add_bounds(AddBounds::None);
+    Ok(s.gen_impl(quote! {
         extern crate nocturne;
 
         gen unsafe impl<'__root> nocturne::raw::Reroot<'__root> for @Self where
+            #(#param_bounds,)*
+            #(#where_bounds,)*
             #(#bounds,)*
         {
             type Rerooted = #rerooted;
         }
-    })
+    }))
+}
+
+/// Each type parameter must be rerootable, into a type meeting the parameter's own bounds
+fn param_bounds(s: &Structure, folder: &mut RootFolder) -> Vec<TokenStream> {
+    let generics = &s.ast().generics;
+    generics
+        .type_params()
+        .map(|param| {
+            let ident = &param.ident;
+            let rerooted = folder.rerooted(ident);
+            let mut bounds: Vec<TokenStream> = param
+                .bounds
+                .iter()
+                .filter(|bound| !is_maybe(bound))
+                .map(|bound| {
+                    let bound = fold::fold_type_param_bound(folder, bound.clone());
+                    quote!(#bound)
+                })
+                .collect();
+            if !is_unsized(generics, param) {
+                bounds.insert(0, quote!(Sized));
+            }
+            quote! {
+                #ident: nocturne::raw::Reroot<'__root>,
+                #rerooted: #(#bounds)+*
+            }
+        })
+        .collect()
+}
+
+/// Tell if the parameter is `?Sized`, inline or in the where clause
+fn is_unsized(generics: &Generics, param: &TypeParam) -> bool {
+    let where_bounds = generics
+        .where_clause
+        .iter()
+        .flat_map(|clause| clause.predicates.iter())
+        .filter_map(|predicate| match predicate {
+            WherePredicate::Type(predicate) => match &predicate.bounded_ty {
+                Type::Path(TypePath { qself: None, path }) if path.is_ident(&param.ident) => {
+                    Some(&predicate.bounds)
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .flatten();
+    param.bounds.iter().chain(where_bounds).any(is_maybe)
+}
+
+fn is_maybe(bound: &TypeParamBound) -> bool {
+    match bound {
+        TypeParamBound::Trait(bound) => matches!(bound.modifier, TraitBoundModifier::Maybe(_)),
+        _ => false,
+    }
+}
+
+/// The rerooted type must meet the where clause of the type too
+fn where_bounds(s: &Structure, folder: &mut RootFolder) -> Vec<WherePredicate> {
+    s.ast()
+        .generics
+        .where_clause
+        .iter()
+        .flat_map(|clause| clause.predicates.iter())
+        .map(|predicate| {
+            let mut predicate = predicate.clone();
+            if let WherePredicate::Type(predicate) = &mut predicate {
+                predicate.bounds = predicate
+                    .bounds
+                    .iter()
+                    .filter(|bound| !is_maybe(bound))
+                    .cloned()
+                    .collect();
+            }
+            fold::fold_where_predicate(folder, predicate)
+        })
+        .collect()
 }
 
-fn bounds<'a>(s: &'a Structure) -> impl Iterator<Item = TokenStream> + 'a {
+/// Each field must reroot to its type in `Rerooted`, whatever `#[gc(bound = "..")]` says
+fn bounds(s: &Structure, folder: &mut RootFolder) -> Vec<TokenStream> {
     s.variants()
-        .into_iter()
+        .iter()
         .flat_map(|variant| variant.bindings())
-        .map(|b| field_where_clause(b.ast()))
+        .map(|b| field_where_clause(b.ast(), folder))
+        .collect()
 }
 
-fn field_where_clause(field: &Field) -> TokenStream {
+fn field_where_clause(field: &Field, folder: &mut RootFolder) -> TokenStream {
     let ty = &field.ty;
-    let rerooted_ty = fold::fold_type(&mut RootFolder, ty.clone());
+    let rerooted_ty = fold::fold_type(folder, ty.clone());
     quote! {
         #ty: nocturne::raw::Reroot<'__root, Rerooted = #rerooted_ty>
     }
 }
 
-fn rerooted(s: &Structure) -> PathSegment {
-    fold::fold_path_segment(&mut RootFolder, self_type(s))
-}
-
 fn self_type(s: &Structure) -> PathSegment {
     let ident = s.ast().ident.clone();
     let args = s
@@ -46,14 +130,15 @@ fn self_type(s: &Structure) -> PathSegment {
         .iter()
         .map(|param| match param {
             GenericParam::Lifetime(lt) => GenericArgument::Lifetime(lt.lifetime.clone()),
-            GenericParam::Type(ty) => {
-                let ty = &ty.ident;
-                GenericArgument::Type(parse2(quote!(#ty)).unwrap())
-            }
-            GenericParam::Const(konst) => {
-                let konst = &konst.ident;
-                GenericArgument::Const(parse2(quote!(#konst)).unwrap())
-            }
+            GenericParam::Type(ty) => GenericArgument::Type(Type::Path(TypePath {
+                qself: None,
+                path: ty.ident.clone().into(),
+            })),
+            GenericParam::Const(konst) => GenericArgument::Const(Expr::Path(ExprPath {
+                attrs: Vec::new(),
+                qself: None,
+                path: konst.ident.clone().into(),
+            })),
         })
         .collect();
 
@@ -68,9 +153,49 @@ fn self_type(s: &Structure) -> PathSegment {
     }
 }
 
-pub struct RootFolder;
+/// Replaces `'root` with `'__root`, and each type parameter with its rerooted type
+pub struct RootFolder {
+    params: Vec<Ident>,
+    error: Option<Error>,
+}
+
+impl RootFolder {
+    fn new(s: &Structure) -> RootFolder {
+        let params = s
+            .ast()
+            .generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect();
+        RootFolder {
+            params,
+            error: None,
+        }
+    }
+
+    fn rerooted(&self, ident: &Ident) -> Type {
+        parse_quote!(<#ident as nocturne::raw::Reroot<'__root>>::Rerooted)
+    }
+}
 
 impl fold::Fold for RootFolder {
+    fn fold_type(&mut self, ty: Type) -> Type {
+        match &ty {
+            Type::Path(TypePath { qself: None, path }) => match path.get_ident() {
+                Some(ident) if self.params.contains(ident) => self.rerooted(ident),
+                // `T::Assoc` can't be rerooted without knowing the trait it comes from
+                None if self.params.contains(&path.segments[0].ident) => {
+                    self.error.get_or_insert_with(|| {
+                        Error::new_spanned(&ty, "expected `<T as Trait>::Assoc`")
+                    });
+                    ty
+                }
+                _ => fold::fold_type(self, ty),
+            },
+            _ => fold::fold_type(self, ty),
+        }
+    }
+
     fn fold_lifetime(&mut self, lifetime: Lifetime) -> Lifetime {
         if lifetime.ident == "root" {
             Lifetime::new("'__root", Span::call_site())
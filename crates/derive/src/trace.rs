@@ -8,29 +8,48 @@ enum HasDrop {
     UnsafeDrop,
 }
 
-pub fn trace_impl(s: &Structure) -> TokenStream {
+pub fn trace_impl(s: &Structure) -> Result<TokenStream> {
+    // `#[gc(bound = "..")]` replaces the bounds inferred from the fields
+    let mut s = s.clone();
+    if let Some(bound) = super::type_bound(&s)? {
+        s.add_bounds(AddBounds::None);
+        for predicate in bound {
+            s.add_where_predicate(predicate);
+        }
+    }
+    let s = &s;
+
     let mark_body = s.each(|b| quote!(#b.mark()));
     let manage_body = s.each(|b| quote!(#b.manage()));
     let finalize_body = s
         .clone()
         .bind_with(|_| BindStyle::RefMut)
         .each(|b| quote!(#b.finalize()));
-    let drop = has_drop(s);
+    let visit_body = s.each(|b| match &b.ast().ident {
+        Some(name) => {
+            let name = name.to_string();
+            quote! {
+                visitor.enter_field(#name);
+                #b.visit(visitor);
+                visitor.leave_field();
+            }
+        }
+        None => quote!(#b.visit(visitor)),
+    });
+    let external_size_body = s.fold(quote!(0), |acc, b| quote!(#acc + #b.external_size()));
+    let drop = has_drop(s)?;
     let drop_glue = match &drop {
         HasDrop::None => quote!(),
         _ => quote!(nocturne::Finalize::finalize(self)),
     };
     let bound = match &drop {
         HasDrop::Drop => {
-            assert!(
-                only_has_root_lifetime(s),
-                "GC'd objects with lifetimes other than 'root must use UnsafeFinalize"
-            );
+            check_only_root_lifetime(s)?;
             quote! { for<'__root> Self: nocturne::raw::Reroot<'__root> }
         }
         _ => quote! {},
     };
-    s.gen_impl(quote! {
+    Ok(s.gen_impl(quote! {
         extern crate nocturne;
 
         gen unsafe impl nocturne::raw::Trace for @Self where
@@ -46,32 +65,41 @@ unsafe fn finalize(&mut self) {
                 match self { #finalize_body }
                 #drop_glue
             }
+            fn visit(&self, visitor: &mut dyn nocturne::raw::Visitor) {
+                match self { #visit_body }
+            }
+            fn external_size(&self) -> usize {
+                match self { #external_size_body }
+            }
         }
-    })
+    }))
 }
 
-fn has_drop(s: &Structure) -> HasDrop {
+fn has_drop(s: &Structure) -> Result<HasDrop> {
     let finalize = super::has_attr(s, "finalize");
-    let unsafe_finalize = super::has_attr(s, "unsafe_finalize");
+    let unsafe_finalize = super::find_arg(&s.ast().attrs, "unsafe_finalize");
     match (finalize, unsafe_finalize) {
-        (true, true) => panic!("type cannot have both finalize & unsafe_finalize attributes"),
-        (true, false) => HasDrop::Drop,
-        (false, true) => HasDrop::UnsafeDrop,
-        (false, false) => HasDrop::None,
+        (true, Some(unsafe_finalize)) => Err(Error::new_spanned(
+            unsafe_finalize,
+            "type cannot have both finalize & unsafe_finalize attributes",
+        )),
+        (true, None) => Ok(HasDrop::Drop),
+        (false, Some(_)) => Ok(HasDrop::UnsafeDrop),
+        (false, None) => Ok(HasDrop::None),
     }
 }
 
-fn only_has_root_lifetime(s: &Structure) -> bool {
-    s.ast()
+fn check_only_root_lifetime(s: &Structure) -> Result<()> {
+    let other = s
+        .ast()
         .generics
-        .params
-        .iter()
-        .filter_map(|param| {
-            if let GenericParam::Lifetime(LifetimeDef { lifetime, .. }) = param {
-                Some(lifetime)
-            } else {
-                None
-            }
-        })
-        .all(|l| l.ident == "root")
+        .lifetimes()
+        .find(|def| def.lifetime.ident != "root");
+    match other {
+        Some(def) => Err(Error::new_spanned(
+            &def.lifetime,
+            "GC'd objects with lifetimes other than 'root must use UnsafeFinalize",
+        )),
+        None => Ok(()),
+    }
 }
@@ -1,10 +1,20 @@
-use crate::{list::List, trace::Trace};
+use crate::{
+    list::List,
+    snapshot::ObjectInfo,
+    trace::{Trace, Visitor},
+};
 use log::*;
+#[cfg(feature = "allocation-profile")]
+use std::panic::Location;
 use std::{
-    alloc::{Allocator, Global},
+    alloc::{AllocError, Allocator, Global, Layout},
     cell::Cell,
-    mem,
-    ptr::NonNull,
+    error::Error,
+    fmt,
+    mem::{self, MaybeUninit},
+    panic::{self, AssertUnwindSafe},
+    ptr::{self, NonNull},
+    thread,
 };
 
 pub struct Data {
@@ -31,11 +41,54 @@ pub struct Allocation<T: ?Sized, A: Allocator = Global> {
 struct Header<A: Allocator = Global> {
     list: List<Allocation<Data, A>>,
     vtable: *mut Vtable,
+    layout: Layout,
+    id: Cell<u64>,
     marked: Cell<bool>,
+    #[cfg(feature = "allocation-profile")]
+    location: &'static Location<'static>,
     allocator: A,
 }
 
+/// The error returned when a fallible GC allocation fails
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GcAllocError {
+    /// The allocation would exceed the maximum heap size, even after collecting garbage
+    HeapLimitExceeded {
+        requested: usize,
+        heap_size: usize,
+        max_heap_size: usize,
+    },
+    /// The allocator could not provide the memory
+    AllocFailed,
+}
+
+impl From<AllocError> for GcAllocError {
+    fn from(_: AllocError) -> GcAllocError {
+        GcAllocError::AllocFailed
+    }
+}
+
+impl fmt::Display for GcAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GcAllocError::HeapLimitExceeded {
+                requested,
+                heap_size,
+                max_heap_size,
+            } => write!(
+                f,
+                "allocating {} bytes would exceed the maximum heap size ({} of {} bytes in use)",
+                requested, heap_size, max_heap_size
+            ),
+            GcAllocError::AllocFailed => f.write_str("memory allocation failed"),
+        }
+    }
+}
+
+impl Error for GcAllocError {}
+
 impl<T: Trace> Allocation<T> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn new(data: T) -> NonNull<Allocation<T>> {
         let vtable = extract_vtable(&data);
 
@@ -43,7 +96,11 @@ pub fn new(data: T) -> NonNull<Allocation<T>> {
             header: Header {
                 list: List::default(),
                 vtable,
+                layout: Layout::new::<Allocation<T>>(),
+                id: Cell::new(0),
                 marked: Cell::new(false),
+                #[cfg(feature = "allocation-profile")]
+                location: Location::caller(),
                 allocator: Global,
             },
             data,
@@ -53,11 +110,54 @@ pub fn new(data: T) -> NonNull<Allocation<T>> {
 }
 
 impl<T: Trace, A: Allocator> Allocation<T, A> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn new_in(data: T, allocator: A) -> NonNull<Allocation<T, A>> {
+        Allocation::init(Box::new_uninit_in(allocator), data)
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_new_in(data: T, allocator: A) -> Result<NonNull<Allocation<T, A>>, AllocError> {
+        Ok(Allocation::init(Box::try_new_uninit_in(allocator)?, data))
+    }
+
+    /// Allocate an object whose data is written later with `write_data`
+    ///
+    /// Until then the object has no vtable, and its data is neither traced nor finalized.
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn new_uninit_in(allocator: A) -> NonNull<Allocation<T, A>> {
+        let (allocation, allocator) =
+            Box::into_raw_with_allocator(Box::<Self, A>::new_uninit_in(allocator));
+        let allocation = allocation as *mut Allocation<T, A>;
+
+        unsafe {
+            ptr::addr_of_mut!((*allocation).header).write(Header {
+                list: List::default(),
+                vtable: ptr::null_mut(),
+                layout: Layout::new::<Allocation<T, A>>(),
+                id: Cell::new(0),
+                marked: Cell::new(false),
+                #[cfg(feature = "allocation-profile")]
+                location: Location::caller(),
+                allocator,
+            });
+            NonNull::new_unchecked(allocation)
+        }
+    }
+
+    /// Initialize the data of an object allocated with `new_uninit_in`
+    pub unsafe fn write_data(self: *mut Allocation<T, A>, data: T) {
+        let allocation = self;
+        let vtable = extract_vtable(&data);
+        ptr::addr_of_mut!((*allocation).data).write(data);
+        (*allocation).header.vtable = vtable;
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    fn init(uninit: Box<MaybeUninit<Allocation<T, A>>, A>, data: T) -> NonNull<Allocation<T, A>> {
         let vtable = extract_vtable(&data);
 
         // Create unitialized memory then initialize it, so we don't have to clone allocator
-        let (allocation, allocator) = Box::into_raw_with_allocator(Box::new_uninit_in(allocator));
+        let (allocation, allocator) = Box::into_raw_with_allocator(uninit);
 
         unsafe {
             NonNull::new_unchecked(allocation)
@@ -66,7 +166,11 @@ pub fn new_in(data: T, allocator: A) -> NonNull<Allocation<T, A>> {
                     header: Header {
                         list: List::default(),
                         vtable,
+                        layout: Layout::new::<Allocation<T, A>>(),
+                        id: Cell::new(0),
                         marked: Cell::new(false),
+                        #[cfg(feature = "allocation-profile")]
+                        location: Location::caller(),
                         allocator,
                     },
                     data,
@@ -80,12 +184,71 @@ impl<T: ?Sized, A: Allocator> Allocation<T, A> {
     pub fn allocator(&self) -> &A {
         &self.header.allocator
     }
+
+    /// The size of the whole allocation, header included
+    pub fn size(&self) -> usize {
+        self.header.layout.size()
+    }
+
+    /// Serial number given to the object when it became managed
+    pub fn id(&self) -> u64 {
+        self.header.id.get()
+    }
+
+    pub(crate) fn set_id(&self, id: u64) {
+        self.header.id.set(id)
+    }
+
+    /// The name of the type of the object
+    pub fn type_name(&self) -> &'static str {
+        if self.is_initialized() {
+            self.dyn_data().type_name()
+        } else {
+            "(uninitialized)"
+        }
+    }
+
+    /// Tell if the data was written, which is always the case unless allocated uninitialized
+    pub fn is_initialized(&self) -> bool {
+        !self.header.vtable.is_null()
+    }
+
+    /// The identity, type and size of the object
+    pub fn info(&self) -> ObjectInfo {
+        ObjectInfo {
+            id: self.id(),
+            address: self.erased() as *const _ as usize,
+            type_name: self.type_name(),
+            size: self.size(),
+        }
+    }
+
+    /// Report the GC pointers held by the object
+    pub fn visit(&self, visitor: &mut dyn Visitor) {
+        if self.is_initialized() {
+            self.dyn_data().visit(visitor)
+        }
+    }
+
+    /// Where the object was allocated
+    #[cfg(feature = "allocation-profile")]
+    pub fn location(&self) -> &'static Location<'static> {
+        self.header.location
+    }
 }
 
 impl<A: Allocator> Allocation<Data, A> {
-    pub unsafe fn free(self: *mut Allocation<Data, A>) {
-        (&mut *self).dyn_data_mut().finalize();
-        drop(Box::from_raw_in(self, (&*self).allocator()))
+    /// Finalize and deallocate the object
+    ///
+    /// The memory is released even if the finalizer panics, in which case the panic is returned.
+    pub unsafe fn free(self: *mut Allocation<Data, A>) -> thread::Result<()> {
+        let finalized = panic::catch_unwind(AssertUnwindSafe(|| {
+            if (*self).is_initialized() {
+                (&mut *self).dyn_data_mut().finalize()
+            }
+        }));
+        drop(Box::from_raw_in(self, (&*self).allocator()));
+        finalized
     }
 }
 
@@ -95,7 +258,12 @@ pub unsafe fn mark(&self) {
             "MARKING object at: {:x}",
             self.erased() as *const _ as usize
         );
-        if !self.header.marked.replace(true) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            object = self.erased() as *const _ as usize,
+            "marking object"
+        );
+        if !self.header.marked.replace(true) && self.is_initialized() {
             self.dyn_data().mark()
         }
     }
@@ -104,10 +272,33 @@ pub fn data(&self) -> &T {
         &self.data
     }
 
+    /// Get a pointer to the data without reading it, which works before it is initialized
+    ///
+    /// # Safety
+    ///
+    /// `self` must point to a live allocation
+    pub unsafe fn data_ptr(self: *const Allocation<T, A>) -> *const T {
+        ptr::addr_of!((*self).data)
+    }
+
     pub fn marked(&self) -> bool {
         self.header.marked.replace(false)
     }
 
+    /// Tell if the object is marked, without clearing the mark
+    pub fn is_marked(&self) -> bool {
+        self.header.marked.get()
+    }
+
+    /// Bytes owned by the object outside of the GC heap
+    pub fn external_size(&self) -> usize {
+        if self.is_initialized() {
+            self.dyn_data().external_size()
+        } else {
+            0
+        }
+    }
+
     pub fn is_unmanaged(&self) -> bool {
         self.header.list.is_head()
     }
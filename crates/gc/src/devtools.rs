@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::snapshot::{HeapSnapshot, RootId};
+
+const NODE_FIELDS: usize = 6;
+const NODE_OBJECT: usize = 3;
+const NODE_SYNTHETIC: usize = 9;
+const EDGE_ELEMENT: usize = 1;
+const EDGE_PROPERTY: usize = 2;
+
+const META: &str = r#"{"node_fields":["type","name","id","self_size","edge_count","trace_node_id"],"node_types":[["hidden","array","string","object","code","closure","regexp","number","native","synthetic","concatenated string","sliced string","symbol","bigint"],"string","number","number","number","number"],"edge_fields":["type","name_or_index","to_node"],"edge_types":[["context","element","property","internal","hidden","shortcut","weak"],"string_or_number","node"],"trace_function_info_fields":[],"trace_node_fields":[],"sample_fields":[],"location_fields":[]}"#;
+
+/// Interns the strings of a snapshot, as the format refers to them by index
+#[derive(Default)]
+struct Strings<'a> {
+    index: HashMap<&'a str, usize>,
+    strings: Vec<&'a str>,
+}
+
+impl<'a> Strings<'a> {
+    fn get(&mut self, string: &'a str) -> usize {
+        let strings = &mut self.strings;
+        *self.index.entry(string).or_insert_with(|| {
+            strings.push(string);
+            strings.len() - 1
+        })
+    }
+}
+
+impl HeapSnapshot {
+    /// Write the snapshot in the `.heapsnapshot` format of the V8 engine
+    ///
+    /// The file can be loaded in the memory panel of the Chrome DevTools. Roots hang off a
+    /// synthetic `(GC roots)` node, with an element edge per stack slot and a property edge per
+    /// global, and references held in named fields are labelled with the field name.
+    pub fn write_devtools<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut strings = Strings::default();
+        let mut nodes = Vec::with_capacity((self.objects().len() + 1) * NODE_FIELDS);
+        let mut edges = Vec::new();
+
+        let root_name = strings.get("(GC roots)");
+        nodes.extend([NODE_SYNTHETIC, root_name, 0, 0, self.roots().len(), 0]);
+        for &(id, idx) in self.roots() {
+            let to_node = (idx + 1) * NODE_FIELDS;
+            match id {
+                RootId::Stack(slot) => edges.extend([EDGE_ELEMENT, slot, to_node]),
+                RootId::Global(name) => edges.extend([EDGE_PROPERTY, strings.get(name), to_node]),
+            }
+        }
+
+        for (idx, object) in self.objects().iter().enumerate() {
+            let references = self.references(idx);
+            let name = strings.get(object.type_name);
+            nodes.extend([
+                NODE_OBJECT,
+                name,
+                object.id as usize,
+                object.size,
+                references.len(),
+                0,
+            ]);
+            for (position, (&target, field)) in references.iter().zip(self.fields(idx)).enumerate()
+            {
+                let to_node = (target + 1) * NODE_FIELDS;
+                match field {
+                    Some(field) => edges.extend([EDGE_PROPERTY, strings.get(field), to_node]),
+                    None => edges.extend([EDGE_ELEMENT, position, to_node]),
+                }
+            }
+        }
+
+        write!(
+            out,
+            r#"{{"snapshot":{{"meta":{},"node_count":{},"edge_count":{},"trace_function_count":0}},"#,
+            META,
+            nodes.len() / NODE_FIELDS,
+            edges.len() / 3
+        )?;
+        write!(out, r#""nodes":"#)?;
+        write_numbers(&mut out, &nodes)?;
+        write!(out, r#","edges":"#)?;
+        write_numbers(&mut out, &edges)?;
+        write!(
+            out,
+            r#","trace_function_infos":[],"trace_tree":[],"samples":[],"locations":[],"strings":["#
+        )?;
+        for (idx, string) in strings.strings.iter().enumerate() {
+            if idx > 0 {
+                write!(out, ",")?;
+            }
+            write_string(&mut out, string)?;
+        }
+        write!(out, "]}}")
+    }
+}
+
+fn write_numbers<W: Write>(out: &mut W, numbers: &[usize]) -> io::Result<()> {
+    write!(out, "[")?;
+    for (idx, number) in numbers.iter().enumerate() {
+        if idx > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "{}", number)?;
+    }
+    write!(out, "]")
+}
+
+fn write_string<W: Write>(out: &mut W, string: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    write!(out, "\"")
+}
@@ -0,0 +1,123 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::panic;
+
+use log::*;
+
+type PanicHook = Box<dyn FnMut(Box<dyn Any + Send>)>;
+
+thread_local! {
+    static PANIC_HOOK: RefCell<Option<PanicHook>> = RefCell::new(None);
+}
+
+/// The panics raised by finalizers and collection hooks during a single collection
+///
+/// Every unreachable object is still freed when its finalizer panics, so the heap is left in a
+/// consistent state and the panics can be inspected after the fact.
+pub struct FinalizerPanics {
+    panics: Vec<Box<dyn Any + Send>>,
+}
+
+impl FinalizerPanics {
+    pub(crate) fn check(panics: Vec<Box<dyn Any + Send>>) -> Result<(), FinalizerPanics> {
+        if panics.is_empty() {
+            Ok(())
+        } else {
+            Err(FinalizerPanics { panics })
+        }
+    }
+
+    /// Get the panic payloads, in the order the finalizers ran
+    pub fn panics(&self) -> &[Box<dyn Any + Send>] {
+        &self.panics
+    }
+
+    /// Take the panic payloads, in the order the finalizers ran
+    pub fn into_panics(self) -> Vec<Box<dyn Any + Send>> {
+        self.panics
+    }
+
+    /// Hand every panic to the finalizer panic hook
+    ///
+    /// Without a hook, the first panic is resumed and the others are dropped.
+    pub(crate) fn report(self) {
+        if let Some(panics) = self.report_to_hook() {
+            let first = panics.panics.into_iter().next().unwrap();
+            panic::resume_unwind(first)
+        }
+    }
+
+    /// Hand every panic to the finalizer panic hook, without ever unwinding
+    ///
+    /// Without a hook, or if the hook itself panics, the panics are logged and dropped.
+    pub(crate) fn report_without_unwinding(self) {
+        let unreported = panic::catch_unwind(panic::AssertUnwindSafe(|| self.report_to_hook()));
+        match unreported {
+            Ok(None) => {}
+            Ok(Some(panics)) => error!("{:?}", panics),
+            Err(_) => error!("The finalizer panic hook panicked"),
+        }
+    }
+
+    /// Hand every panic to the finalizer panic hook, giving them back if there is none
+    fn report_to_hook(self) -> Option<FinalizerPanics> {
+        let hook = PANIC_HOOK.with(|hook| hook.borrow_mut().take());
+        match hook {
+            Some(mut hook) => {
+                self.panics.into_iter().for_each(&mut hook);
+                // Put the hook back unless it was replaced while running
+                PANIC_HOOK.with(|slot| {
+                    slot.borrow_mut().get_or_insert(hook);
+                });
+                None
+            }
+            None => Some(self),
+        }
+    }
+}
+
+impl fmt::Debug for FinalizerPanics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages: Vec<&str> = self.panics.iter().map(|p| panic_message(&**p)).collect();
+        f.debug_tuple("FinalizerPanics").field(&messages).finish()
+    }
+}
+
+impl fmt::Display for FinalizerPanics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} finalizer(s) panicked during collection",
+            self.panics.len()
+        )
+    }
+}
+
+impl Error for FinalizerPanics {}
+
+/// Set the hook called by `collect` for each panicking finalizer on this thread
+///
+/// Returns the previous hook, if any.
+pub fn set_finalizer_panic_hook<F>(hook: F) -> Option<PanicHook>
+where
+    F: FnMut(Box<dyn Any + Send>) + 'static,
+{
+    PANIC_HOOK.with(|slot| slot.borrow_mut().replace(Box::new(hook)))
+}
+
+/// Remove the finalizer panic hook of this thread, restoring the default of resuming the panic
+pub fn take_finalizer_panic_hook() -> Option<PanicHook> {
+    PANIC_HOOK.with(|slot| slot.borrow_mut().take())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
@@ -0,0 +1,195 @@
+use std::cell::{BorrowError, BorrowMutError, Cell, Ref, RefCell, RefMut};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use crate::trace::{NullTrace, Trace, Visitor};
+
+thread_local! {
+    static DEFERRED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Tell if a `GcCell` is mutably borrowed on this thread, in which case nothing may be traced
+pub(crate) fn collection_deferred() -> bool {
+    DEFERRED.with(|deferred| deferred.get() != 0)
+}
+
+/// Defers collections on this thread for as long as it lives
+struct DeferCollection {
+    _marker: PhantomData<*const ()>,
+}
+
+impl DeferCollection {
+    fn new() -> DeferCollection {
+        DEFERRED.with(|deferred| deferred.set(deferred.get() + 1));
+        DeferCollection {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for DeferCollection {
+    fn drop(&mut self) {
+        DEFERRED.with(|deferred| deferred.set(deferred.get() - 1));
+    }
+}
+
+/// A `RefCell` whose contents can hold GC pointers
+///
+/// Collections are deferred while any `GcCell` is mutably borrowed, so the collector never
+/// traces data that is being written. When a mutable borrow of a managed cell ends, the pointers
+/// it holds are managed, as objects stored in the cell may not have been yet.
+pub struct GcCell<T: ?Sized> {
+    managed: Cell<bool>,
+    value: RefCell<T>,
+}
+
+impl<T> GcCell<T> {
+    pub fn new(value: T) -> GcCell<T> {
+        GcCell {
+            managed: Cell::new(false),
+            value: RefCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Trace + ?Sized> GcCell<T> {
+    /// Immutably borrow the contents, panicking if they are mutably borrowed
+    pub fn borrow(&self) -> GcCellRef<'_, T> {
+        self.try_borrow()
+            .expect("GcCell is already mutably borrowed")
+    }
+
+    pub fn try_borrow(&self) -> Result<GcCellRef<'_, T>, BorrowError> {
+        Ok(GcCellRef {
+            value: self.value.try_borrow()?,
+        })
+    }
+
+    /// Mutably borrow the contents, panicking if they are borrowed
+    ///
+    /// No collection happens until the borrow ends.
+    pub fn borrow_mut(&self) -> GcCellRefMut<'_, T> {
+        self.try_borrow_mut().expect("GcCell is already borrowed")
+    }
+
+    pub fn try_borrow_mut(&self) -> Result<GcCellRefMut<'_, T>, BorrowMutError> {
+        Ok(GcCellRefMut {
+            value: self.value.try_borrow_mut()?,
+            managed: &self.managed,
+            _defer: DeferCollection::new(),
+        })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for GcCell<T> {
+    fn default() -> GcCell<T> {
+        GcCell::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for GcCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value.try_borrow() {
+            Ok(value) => f.debug_struct("GcCell").field("value", &&*value).finish(),
+            Err(_) => f.write_str("GcCell { <borrowed> }"),
+        }
+    }
+}
+
+unsafe impl<T: Trace + ?Sized> Trace for GcCell<T> {
+    unsafe fn mark(&self) {
+        // Collections are deferred while the cell is mutably borrowed
+        self.value.borrow().mark()
+    }
+
+    unsafe fn manage(&self) {
+        self.managed.set(true);
+        // A mutable borrow manages the contents once it ends
+        if let Ok(value) = self.value.try_borrow() {
+            value.manage()
+        }
+    }
+
+    unsafe fn finalize(&mut self) {
+        self.value.get_mut().finalize()
+    }
+
+    fn external_size(&self) -> usize {
+        self.value
+            .try_borrow()
+            .map_or(0, |value| value.external_size())
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        // The contents are skipped while the cell is mutably borrowed
+        if let Ok(value) = self.value.try_borrow() {
+            value.visit(visitor)
+        }
+    }
+}
+
+unsafe impl<T: NullTrace + ?Sized> NullTrace for GcCell<T> {}
+
+/// An immutable borrow of the contents of a `GcCell`
+pub struct GcCellRef<'a, T: ?Sized> {
+    value: Ref<'a, T>,
+}
+
+impl<'a, T: ?Sized> Deref for GcCellRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'a, T: fmt::Debug + ?Sized> fmt::Debug for GcCellRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        T::fmt(&self.value, f)
+    }
+}
+
+/// A mutable borrow of the contents of a `GcCell`, during which collections are deferred
+pub struct GcCellRefMut<'a, T: Trace + ?Sized> {
+    value: RefMut<'a, T>,
+    managed: &'a Cell<bool>,
+    _defer: DeferCollection,
+}
+
+impl<'a, T: Trace + ?Sized> Deref for GcCellRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'a, T: Trace + ?Sized> DerefMut for GcCellRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<'a, T: fmt::Debug + Trace + ?Sized> fmt::Debug for GcCellRefMut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        T::fmt(&self.value, f)
+    }
+}
+
+impl<'a, T: Trace + ?Sized> Drop for GcCellRefMut<'a, T> {
+    fn drop(&mut self) {
+        // The write barrier: a managed cell must not hold unmanaged objects
+        if self.managed.get() {
+            unsafe { self.value.manage() }
+        }
+    }
+}
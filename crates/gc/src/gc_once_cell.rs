@@ -0,0 +1,107 @@
+use std::cell::{Cell, OnceCell};
+use std::fmt;
+
+use crate::trace::{NullTrace, Trace, Visitor};
+
+/// A `OnceCell` whose contents can hold GC pointers, for fields that are computed lazily
+///
+/// Once a managed cell is initialized, the pointers it holds are managed.
+pub struct GcOnceCell<T> {
+    managed: Cell<bool>,
+    value: OnceCell<T>,
+}
+
+impl<T> GcOnceCell<T> {
+    pub fn new() -> GcOnceCell<T> {
+        GcOnceCell {
+            managed: Cell::new(false),
+            value: OnceCell::new(),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Trace> GcOnceCell<T> {
+    /// Initialize the cell, giving the value back if it already was
+    pub fn set(&self, value: T) -> Result<(), T> {
+        self.value.set(value)?;
+        self.initialized();
+        Ok(())
+    }
+
+    /// Get the contents, initializing them with `f` if needed
+    ///
+    /// Collections may happen while `f` runs, during which the cell is empty. Panics if `f`
+    /// initializes the cell itself.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        let mut initialized = false;
+        let value = self.value.get_or_init(|| {
+            initialized = true;
+            f()
+        });
+        if initialized {
+            self.initialized();
+        }
+        value
+    }
+
+    fn initialized(&self) {
+        if self.managed.get() {
+            unsafe { self.value.get().unwrap().manage() }
+        }
+    }
+}
+
+impl<T> Default for GcOnceCell<T> {
+    fn default() -> GcOnceCell<T> {
+        GcOnceCell::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for GcOnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("GcOnceCell")
+            .field(&self.value.get())
+            .finish()
+    }
+}
+
+unsafe impl<T: Trace> Trace for GcOnceCell<T> {
+    unsafe fn mark(&self) {
+        if let Some(value) = self.value.get() {
+            value.mark()
+        }
+    }
+
+    unsafe fn manage(&self) {
+        self.managed.set(true);
+        if let Some(value) = self.value.get() {
+            value.manage()
+        }
+    }
+
+    unsafe fn finalize(&mut self) {
+        if let Some(value) = self.value.get_mut() {
+            value.finalize()
+        }
+    }
+
+    fn external_size(&self) -> usize {
+        self.value.get().map_or(0, |value| value.external_size())
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        if let Some(value) = self.value.get() {
+            value.visit(visitor)
+        }
+    }
+}
+
+unsafe impl<T: NullTrace> NullTrace for GcOnceCell<T> {}
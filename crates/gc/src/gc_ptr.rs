@@ -1,15 +1,18 @@
-use std::alloc::{Allocator, Global};
+use std::alloc::{AllocError, Allocator, Global};
+use std::marker::Unsize;
+use std::ops::CoerceUnsized;
 use std::pin::Pin;
 use std::ptr::NonNull;
 
 use crate::alloc::{Allocation, Data};
-use crate::trace::Trace;
+use crate::trace::{Trace, Visitor};
 
 pub struct GcPtr<T: ?Sized, A: Allocator = Global> {
     inner: NonNull<Allocation<T, A>>,
 }
 
 impl<T: Trace> GcPtr<T> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub(crate) fn new(data: T) -> GcPtr<T> {
         GcPtr {
             inner: Allocation::new(data),
@@ -18,11 +21,35 @@ pub(crate) fn new(data: T) -> GcPtr<T> {
 }
 
 impl<T: Trace, A: Allocator> GcPtr<T, A> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub(crate) fn new_in(data: T, allocator: A) -> GcPtr<T, A> {
         GcPtr {
             inner: Allocation::new_in(data, allocator),
         }
     }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub(crate) fn new_uninit_in(allocator: A) -> GcPtr<T, A> {
+        GcPtr {
+            inner: Allocation::new_uninit_in(allocator),
+        }
+    }
+
+    /// Initialize the data of a pointer returned by `alloc_uninit`
+    ///
+    /// # Safety
+    ///
+    /// The data must not have been initialized yet
+    pub(crate) unsafe fn write(self, data: T) {
+        self.inner.as_ptr().write_data(data)
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub(crate) fn try_new_in(data: T, allocator: A) -> Result<GcPtr<T, A>, AllocError> {
+        Ok(GcPtr {
+            inner: Allocation::try_new_in(data, allocator)?,
+        })
+    }
 }
 
 impl<T: ?Sized, A: Allocator> GcPtr<T, A> {
@@ -35,9 +62,24 @@ pub unsafe fn allocator(&self) -> &A {
 
     /// Get a reference to the GC'd data
     ///
-    /// Invariants: GcPtr must not be dangling
+    /// Invariants: GcPtr must not be dangling. Panics if the data was allocated uninitialized and
+    /// has not been written yet.
     pub unsafe fn data(&self) -> &T {
-        self.inner.as_ref().data()
+        let allocation = self.inner.as_ref();
+        assert!(
+            allocation.is_initialized(),
+            "Cannot read an object before it is initialized"
+        );
+        allocation.data()
+    }
+
+    /// Get a raw pointer to the GC'd data, which may not be initialized yet
+    ///
+    /// # Safety
+    ///
+    /// GcPtr must not be dangling
+    pub unsafe fn data_ptr(&self) -> *const T {
+        (self.inner.as_ptr() as *const Allocation<T, A>).data_ptr()
     }
 
     /// Tell if this ptr is managed or not
@@ -54,6 +96,17 @@ pub unsafe fn deallocate(self) {
         drop(Box::from_raw_in(self.inner.as_ptr(), self.allocator()))
     }
 
+    /// Reinterpret the pointer as pointing to a `U`
+    ///
+    /// # Safety
+    ///
+    /// The data must be a valid `U`
+    pub unsafe fn cast<U>(self) -> GcPtr<U, A> {
+        GcPtr {
+            inner: self.inner.cast(),
+        }
+    }
+
     pub(crate) fn erased(self) -> NonNull<Allocation<Data, A>> {
         unsafe { NonNull::new_unchecked(self.inner.as_ptr() as *mut Allocation<Data, A>) }
     }
@@ -73,6 +126,10 @@ unsafe fn manage(&self) {
     }
 
     unsafe fn finalize(&mut self) {}
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        visitor.edge(self.inner.cast())
+    }
 }
 
 impl<T: ?Sized, A: Allocator> Clone for GcPtr<T, A> {
@@ -82,3 +139,5 @@ fn clone(&self) -> GcPtr<T, A> {
 }
 
 impl<T: ?Sized, A: Allocator> Copy for GcPtr<T, A> {}
+
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Allocator> CoerceUnsized<GcPtr<U, A>> for GcPtr<T, A> {}
@@ -0,0 +1,72 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use crate::stats::GcStats;
+
+/// A point of a collection at which hooks are called
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GcEvent {
+    /// Before tracing from the roots
+    BeforeMark,
+    /// After every reachable object has been marked
+    ///
+    /// Objects allocated or rooted by this hook, or by `BeforeSweep` ones, survive the sweep.
+    AfterMark,
+    /// Before freeing the unreachable objects
+    BeforeSweep,
+    /// Once the collection is over, finalizers included
+    AfterCollect,
+}
+
+/// Identifies a registered hook so that it can be removed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HookId(usize);
+
+type Hook = Rc<RefCell<dyn FnMut(&GcStats)>>;
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    next_id: Cell<usize>,
+    hooks: RefCell<Vec<(HookId, GcEvent, Hook)>>,
+}
+
+impl Hooks {
+    pub fn add<F: FnMut(&GcStats) + 'static>(&self, event: GcEvent, hook: F) -> HookId {
+        let id = HookId(self.next_id.get());
+        self.next_id.set(id.0 + 1);
+        self.hooks
+            .borrow_mut()
+            .push((id, event, Rc::new(RefCell::new(hook))));
+        id
+    }
+
+    pub fn remove(&self, id: HookId) -> bool {
+        let mut hooks = self.hooks.borrow_mut();
+        let len = hooks.len();
+        hooks.retain(|(hook_id, _, _)| *hook_id != id);
+        hooks.len() != len
+    }
+
+    /// Call every hook registered for `event`, returning the panics they raised
+    ///
+    /// No borrow is held while the hooks run, so they may add or remove hooks.
+    pub fn fire(&self, event: GcEvent, stats: &GcStats) -> Vec<Box<dyn Any + Send>> {
+        let hooks: Vec<Hook> = self
+            .hooks
+            .borrow()
+            .iter()
+            .filter(|(_, hook_event, _)| *hook_event == event)
+            .map(|(_, _, hook)| hook.clone())
+            .collect();
+
+        hooks
+            .into_iter()
+            .filter_map(|hook| {
+                let mut hook = hook.try_borrow_mut().ok()?;
+                panic::catch_unwind(AssertUnwindSafe(|| (*hook)(stats))).err()
+            })
+            .collect()
+    }
+}
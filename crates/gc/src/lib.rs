@@ -1,38 +1,90 @@
-#![feature(arbitrary_self_types, allocator_api)]
+#![feature(arbitrary_self_types, allocator_api, coerce_unsized, unsize)]
 
 mod alloc;
+mod devtools;
+mod finalizer;
+mod gc_cell;
+mod gc_once_cell;
 mod gc_ptr;
+mod hooks;
 mod list;
+#[cfg(feature = "allocation-profile")]
+mod profile;
 mod root;
+mod snapshot;
 mod state;
+mod stats;
 mod trace;
 
 use anymap::AnyMap;
-use std::alloc::{Allocator, Global};
+use std::alloc::{Allocator, Global, Layout};
 use std::cell::RefCell;
 use std::pin::Pin;
 
+use crate::alloc::Allocation;
 use crate::state::GcState;
 
+pub use crate::alloc::GcAllocError;
+pub use crate::finalizer::{set_finalizer_panic_hook, take_finalizer_panic_hook, FinalizerPanics};
+pub use crate::gc_cell::{GcCell, GcCellRef, GcCellRefMut};
+pub use crate::gc_once_cell::GcOnceCell;
 pub use crate::gc_ptr::GcPtr;
-pub use crate::root::Root;
-pub use crate::trace::{NullTrace, Trace};
+pub use crate::hooks::{GcEvent, HookId};
+#[cfg(feature = "allocation-profile")]
+pub use crate::profile::AllocationSite;
+pub use crate::root::{Root, RootSegment};
+pub use crate::snapshot::{
+    HeapDiff, HeapSnapshot, LeakedObject, ObjectInfo, RetainingPath, RootId,
+};
+pub use crate::stats::GcStats;
+pub use crate::trace::{NullTrace, Trace, Visitor};
 
 thread_local! {
     static GCMAP: RefCell<AnyMap> = RefCell::new(AnyMap::new());
 }
 
 /// Allocate an unmanaged GcPtr
+#[cfg_attr(feature = "allocation-profile", track_caller)]
 pub fn alloc_unmanaged<T: Trace>(data: T) -> GcPtr<T> {
     GcPtr::new(data)
 }
 
 /// Allocate an unmanaged GcPtr
+#[cfg_attr(feature = "allocation-profile", track_caller)]
 pub fn alloc_unmanaged_in<T: Trace, A: Allocator>(data: T, allocator: A) -> GcPtr<T, A> {
     GcPtr::new_in(data, allocator)
 }
 
+/// Allocate a managed GcPtr whose data is written later with `write`
+///
+/// Until then the object is not traced, and it is freed without being finalized if it becomes
+/// unreachable.
+#[cfg_attr(feature = "allocation-profile", track_caller)]
+pub fn alloc_uninit<T: Trace>() -> GcPtr<T> {
+    alloc_uninit_in(Global)
+}
+
+/// Allocate a managed GcPtr whose data is written later with `write`
+#[cfg_attr(feature = "allocation-profile", track_caller)]
+pub fn alloc_uninit_in<T: Trace, A: Allocator + 'static>(allocator: A) -> GcPtr<T, A> {
+    let gc_ptr = GcPtr::new_uninit_in(allocator);
+    unsafe {
+        manage(gc_ptr);
+    }
+    gc_ptr
+}
+
+/// Initialize the data of a GcPtr returned by `alloc_uninit`
+///
+/// # Safety
+///
+/// `ptr` must come from `alloc_uninit`, not be dangling and not be initialized yet
+pub unsafe fn write<T: Trace, A: Allocator + 'static>(ptr: GcPtr<T, A>, data: T) {
+    with_gc(|gc| gc.write(ptr, data))
+}
+
 /// Allocate a managed GcPtr
+#[cfg_attr(feature = "allocation-profile", track_caller)]
 pub fn alloc<T: Trace>(data: T) -> GcPtr<T> {
     let gc_ptr = alloc_unmanaged(data);
     unsafe {
@@ -42,6 +94,7 @@ pub fn alloc<T: Trace>(data: T) -> GcPtr<T> {
 }
 
 /// Allocate a managed GcPtr
+#[cfg_attr(feature = "allocation-profile", track_caller)]
 pub fn alloc_in<T: Trace, A: Allocator + 'static>(data: T, allocator: A) -> GcPtr<T, A> {
     let gc_ptr = alloc_unmanaged_in(data, allocator);
     unsafe {
@@ -50,6 +103,82 @@ pub fn alloc_in<T: Trace, A: Allocator + 'static>(data: T, allocator: A) -> GcPt
     gc_ptr
 }
 
+/// Allocate a managed GcPtr, failing if the heap limit would be exceeded
+///
+/// The objects held by `data` that are managed along with it count toward the limit too. When the
+/// limit is hit, garbage is collected before giving up. Finalizer panics raised meanwhile go to the
+/// finalizer panic hook, or are logged if there is none, but never unwind.
+#[cfg_attr(feature = "allocation-profile", track_caller)]
+pub fn try_alloc<T: Trace>(data: T) -> Result<GcPtr<T>, GcAllocError> {
+    try_alloc_in(data, Global)
+}
+
+/// Allocate a managed GcPtr, failing if the heap limit would be exceeded or the allocator fails
+///
+/// The objects held by `data` that are managed along with it count toward the limit too. When the
+/// limit is hit, garbage is collected before giving up. Finalizer panics raised meanwhile go to the
+/// finalizer panic hook, or are logged if there is none, but never unwind.
+#[cfg_attr(feature = "allocation-profile", track_caller)]
+pub fn try_alloc_in<T: Trace, A: Allocator + 'static>(
+    data: T,
+    allocator: A,
+) -> Result<GcPtr<T, A>, GcAllocError> {
+    reserve::<A>(Layout::new::<Allocation<T, A>>().size())?;
+    let gc_ptr = GcPtr::try_new_in(data, allocator)?;
+    unsafe {
+        try_manage(gc_ptr)?;
+    }
+    Ok(gc_ptr)
+}
+
+/// Manage a GcPtr, failing if it and the objects managed along with it exceed the heap limit
+///
+/// Garbage is collected before giving up, and the new objects are freed on failure.
+unsafe fn try_manage<T: Trace, A: Allocator + 'static>(
+    ptr: GcPtr<T, A>,
+) -> Result<(), GcAllocError> {
+    let heap_size = heap_size_with_allocator::<A>();
+    manage(ptr);
+    let requested = heap_size_with_allocator::<A>() - heap_size;
+    if with_gc(|gc: Pin<&GcState<A>>| gc.check_heap_limit(0)).is_ok() {
+        return Ok(());
+    }
+
+    // Keep the new objects alive while making room for them
+    let root = new_root::<A>();
+    set_root(root, ptr);
+    collect_without_unwinding::<A>();
+    pop_root::<A>(root);
+    if with_gc(|gc: Pin<&GcState<A>>| gc.check_heap_limit(0)).is_ok() {
+        return Ok(());
+    }
+
+    collect_without_unwinding::<A>();
+    Err(GcAllocError::HeapLimitExceeded {
+        requested,
+        heap_size: heap_size_with_allocator::<A>(),
+        max_heap_size: max_heap_size_with_allocator::<A>().unwrap_or(usize::MAX),
+    })
+}
+
+fn reserve<A: Allocator + 'static>(bytes: usize) -> Result<(), GcAllocError> {
+    if with_gc(|gc: Pin<&GcState<A>>| gc.should_collect()) {
+        collect_without_unwinding::<A>();
+    }
+    if with_gc(|gc: Pin<&GcState<A>>| gc.check_heap_limit(bytes)).is_ok() {
+        return Ok(());
+    }
+    collect_without_unwinding::<A>();
+    with_gc(|gc: Pin<&GcState<A>>| gc.check_heap_limit(bytes))
+}
+
+/// Collect garbage for a fallible allocation, which reports finalizer panics but never unwinds
+fn collect_without_unwinding<A: Allocator + 'static>() {
+    if let Err(panics) = try_collect_with_allocator::<A>() {
+        panics.report_without_unwinding()
+    }
+}
+
 /// Manage a GcPtr
 ///
 /// Invariants: ptr must not be dangling and must not already be managed
@@ -68,19 +197,231 @@ pub fn count_managed_objects_with_allocator<A: Allocator + 'static>() -> usize {
 }
 
 /// Count roots into the GC
+///
+/// Slots released while a slot above them is still in use are not counted.
 pub fn count_roots() -> usize {
-    with_gc(|gc: Pin<&GcState<Global>>| gc.roots().len())
+    with_gc(|gc: Pin<&GcState<Global>>| gc.count_roots())
 }
 
 /// Count roots into the GC
+///
+/// Slots released while a slot above them is still in use are not counted.
 pub fn count_roots_with_allocator<A: Allocator + 'static>() -> usize {
-    with_gc(|gc: Pin<&GcState<A>>| gc.roots().len())
+    with_gc(|gc: Pin<&GcState<A>>| gc.count_roots())
+}
+
+/// Root `ptr` under `name` until it is unregistered, returning whether a global was replaced
+pub fn register_global<T: Trace + ?Sized + 'static, A: Allocator + 'static>(
+    name: &'static str,
+    ptr: GcPtr<T, A>,
+) -> bool {
+    with_gc(|gc| gc.register_global(name, ptr))
+}
+
+/// Look up the global `name`, `None` if it is missing or of another type
+pub fn global<T: Trace + ?Sized + 'static>(name: &str) -> Option<GcPtr<T>> {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.global(name))
+}
+
+/// Look up the global `name`, `None` if it is missing or of another type
+pub fn global_with_allocator<T: Trace + ?Sized + 'static, A: Allocator + 'static>(
+    name: &str,
+) -> Option<GcPtr<T, A>> {
+    with_gc(|gc: Pin<&GcState<A>>| gc.global(name))
+}
+
+/// Stop rooting the global `name`, returning whether it was registered
+pub fn unregister_global(name: &str) -> bool {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.unregister_global(name))
+}
+
+/// Stop rooting the global `name`, returning whether it was registered
+pub fn unregister_global_with_allocator<A: Allocator + 'static>(name: &str) -> bool {
+    with_gc(|gc: Pin<&GcState<A>>| gc.unregister_global(name))
+}
+
+/// The registered globals, by name
+pub fn globals() -> Vec<(&'static str, ObjectInfo)> {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.globals())
+}
+
+/// The registered globals, by name
+pub fn globals_with_allocator<A: Allocator + 'static>() -> Vec<(&'static str, ObjectInfo)> {
+    with_gc(|gc: Pin<&GcState<A>>| gc.globals())
+}
+
+/// Total size in bytes of the objects managed by the GC
+pub fn heap_size() -> usize {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.heap_size())
+}
+
+/// Total size in bytes of the objects managed by the GC
+pub fn heap_size_with_allocator<A: Allocator + 'static>() -> usize {
+    with_gc(|gc: Pin<&GcState<A>>| gc.heap_size())
+}
+
+/// Get the maximum heap size used by fallible allocations
+pub fn max_heap_size() -> Option<usize> {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.max_heap_size())
+}
+
+/// Get the maximum heap size used by fallible allocations
+pub fn max_heap_size_with_allocator<A: Allocator + 'static>() -> Option<usize> {
+    with_gc(|gc: Pin<&GcState<A>>| gc.max_heap_size())
+}
+
+/// Set the maximum heap size used by fallible allocations, `None` meaning unlimited
+///
+/// Infallible allocations, such as `alloc` and `Root::gc`, count toward it but are never refused.
+pub fn set_max_heap_size(max_heap_size: Option<usize>) {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.set_max_heap_size(max_heap_size))
+}
+
+/// Set the maximum heap size used by fallible allocations, `None` meaning unlimited
+///
+/// Infallible allocations, such as `alloc_in` and `Root::gc_in`, count toward it but are never
+/// refused.
+pub fn set_max_heap_size_with_allocator<A: Allocator + 'static>(max_heap_size: Option<usize>) {
+    with_gc(|gc: Pin<&GcState<A>>| gc.set_max_heap_size(max_heap_size))
+}
+
+/// Get the memory use below which allocations never collect, `None` if they never do
+pub fn collection_threshold() -> Option<usize> {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.collection_threshold())
+}
+
+/// Get the memory use below which allocations never collect, `None` if they never do
+pub fn collection_threshold_with_allocator<A: Allocator + 'static>() -> Option<usize> {
+    with_gc(|gc: Pin<&GcState<A>>| gc.collection_threshold())
+}
+
+/// Let allocations collect garbage once the heap and external memory use reach `threshold`
+///
+/// After each collection, the next one waits until memory use has doubled. `None`, the default,
+/// turns automatic collections off, so that only explicit calls to `collect` free objects.
+pub fn set_collection_threshold(threshold: Option<usize>) {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.set_collection_threshold(threshold))
+}
+
+/// Let allocations collect garbage once the heap and external memory use reach `threshold`
+///
+/// After each collection, the next one waits until memory use has doubled. `None`, the default,
+/// turns automatic collections off, so that only explicit calls to `collect_with_allocator` free
+/// objects.
+pub fn set_collection_threshold_with_allocator<A: Allocator + 'static>(threshold: Option<usize>) {
+    with_gc(|gc: Pin<&GcState<A>>| gc.set_collection_threshold(threshold))
+}
+
+/// Bytes owned by managed objects outside of the heap, including manual memory pressure
+pub fn external_size() -> usize {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.external_size())
+}
+
+/// Bytes owned by managed objects outside of the heap, including manual memory pressure
+pub fn external_size_with_allocator<A: Allocator + 'static>() -> usize {
+    with_gc(|gc: Pin<&GcState<A>>| gc.external_size())
+}
+
+/// Tell the collector that managed objects hold `bytes` more memory it cannot see
+///
+/// This makes the next automatic collection happen sooner. It should be balanced by a call to
+/// `remove_memory_pressure` once the memory is released.
+pub fn add_memory_pressure(bytes: usize) {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.add_memory_pressure(bytes))
+}
+
+/// Tell the collector that managed objects hold `bytes` more memory it cannot see
+///
+/// This makes the next automatic collection happen sooner. It should be balanced by a call to
+/// `remove_memory_pressure_with_allocator` once the memory is released.
+pub fn add_memory_pressure_with_allocator<A: Allocator + 'static>(bytes: usize) {
+    with_gc(|gc: Pin<&GcState<A>>| gc.add_memory_pressure(bytes))
+}
+
+/// Undo a previous `add_memory_pressure`
+pub fn remove_memory_pressure(bytes: usize) {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.remove_memory_pressure(bytes))
+}
+
+/// Undo a previous `add_memory_pressure_with_allocator`
+pub fn remove_memory_pressure_with_allocator<A: Allocator + 'static>(bytes: usize) {
+    with_gc(|gc: Pin<&GcState<A>>| gc.remove_memory_pressure(bytes))
+}
+
+/// Get the current statistics of the GC
+pub fn stats() -> GcStats {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.stats())
+}
+
+/// Get the current statistics of the GC
+pub fn stats_with_allocator<A: Allocator + 'static>() -> GcStats {
+    with_gc(|gc: Pin<&GcState<A>>| gc.stats())
+}
+
+/// Group the live objects by the source location that allocated them, largest first
+///
+/// Only managed objects are counted, so collecting beforehand leaves just the reachable ones.
+#[cfg(feature = "allocation-profile")]
+pub fn allocation_profile() -> Vec<AllocationSite> {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.allocation_profile())
+}
+
+/// Group the live objects by the source location that allocated them, largest first
+///
+/// Only managed objects are counted, so collecting beforehand leaves just the reachable ones.
+#[cfg(feature = "allocation-profile")]
+pub fn allocation_profile_with_allocator<A: Allocator + 'static>() -> Vec<AllocationSite> {
+    with_gc(|gc: Pin<&GcState<A>>| gc.allocation_profile())
+}
+
+/// Find the shortest chain of references from a root to the object behind `ptr`
+///
+/// Returns `None` if the object is unreachable or not managed.
+pub fn retaining_path<T: ?Sized, A: Allocator + 'static>(
+    ptr: GcPtr<T, A>,
+) -> Option<RetainingPath> {
+    with_gc(|gc: Pin<&GcState<A>>| gc.retaining_path(ptr.erased().as_ptr() as *const ()))
+}
+
+/// Register a hook to be called with the current statistics at `event` in every collection
+///
+/// Panics raised by hooks are reported like finalizer panics.
+pub fn add_hook<F: FnMut(&GcStats) + 'static>(event: GcEvent, hook: F) -> HookId {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.add_hook(event, hook))
+}
+
+/// Register a hook to be called with the current statistics at `event` in every collection
+///
+/// Panics raised by hooks are reported like finalizer panics.
+pub fn add_hook_with_allocator<A: Allocator + 'static, F: FnMut(&GcStats) + 'static>(
+    event: GcEvent,
+    hook: F,
+) -> HookId {
+    with_gc(|gc: Pin<&GcState<A>>| gc.add_hook(event, hook))
+}
+
+/// Unregister a hook, returning whether it was registered
+pub fn remove_hook(id: HookId) -> bool {
+    with_gc(|gc: Pin<&GcState<Global>>| gc.remove_hook(id))
+}
+
+/// Unregister a hook, returning whether it was registered
+pub fn remove_hook_with_allocator<A: Allocator + 'static>(id: HookId) -> bool {
+    with_gc(|gc: Pin<&GcState<A>>| gc.remove_hook(id))
 }
 
 fn new_root<A: Allocator + 'static>() -> usize {
     with_gc(|gc: Pin<&GcState<A>>| gc.new_root())
 }
 
+fn new_root_segment<A: Allocator + 'static>() -> usize {
+    with_gc(|gc: Pin<&GcState<A>>| gc.new_root_segment())
+}
+
+fn push_root<T: Trace + ?Sized, A: Allocator + 'static>(idx: usize, ptr: GcPtr<T, A>) {
+    with_gc(|gc| gc.push_root(idx, ptr))
+}
+
 fn set_root<T: Trace + ?Sized, A: Allocator + 'static>(idx: usize, ptr: GcPtr<T, A>) {
     with_gc(|gc| gc.set_root(idx, ptr))
 }
@@ -90,23 +431,65 @@ fn pop_root<A: Allocator + 'static>(idx: usize) {
 }
 
 fn with_gc<T, F: FnOnce(Pin<&GcState<A>>) -> T, A: Allocator + 'static>(f: F) -> T {
-    GCMAP.with(|gcmap| {
+    let gc: *const GcState<A> = GCMAP.with(|gcmap| {
         let mut gcmap = gcmap.borrow_mut();
         let mut gc = gcmap.get();
         if gc.is_none() {
             gcmap.insert(GcState::<A>::default());
             gc = gcmap.get();
         }
-        let gc = gc.unwrap();
-        let gc: Pin<&GcState<A>> = unsafe { Pin::new_unchecked(gc) };
-        f(gc)
-    })
+        gc.unwrap() as *const GcState<A>
+    });
+    // The map boxes its values and states are never removed, so the state stays put for the
+    // lifetime of the thread. Releasing the map borrow lets `f` reenter, e.g. from a finalizer.
+    let gc: Pin<&GcState<A>> = unsafe { Pin::new_unchecked(&*gc) };
+    f(gc)
 }
 
+/// Collect garbage
+///
+/// Panicking finalizers do not interrupt the sweep; their panics are handed to the finalizer
+/// panic hook afterwards, or the first one is resumed if no hook is set. Nothing is collected
+/// while a `GcCell` is mutably borrowed.
 pub fn collect() {
-    with_gc(|gc: Pin<&GcState<Global>>| gc.collect())
+    collect_with_allocator::<Global>()
 }
 
+/// Collect garbage
+///
+/// Panicking finalizers do not interrupt the sweep; their panics are handed to the finalizer
+/// panic hook afterwards, or the first one is resumed if no hook is set. Nothing is collected
+/// while a `GcCell` is mutably borrowed.
 pub fn collect_with_allocator<A: Allocator + 'static>() {
-    with_gc(|gc: Pin<&GcState<A>>| gc.collect())
+    if let Err(panics) = try_collect_with_allocator::<A>() {
+        panics.report()
+    }
+}
+
+/// Collect garbage if the heap and external memory have grown enough since the last collection
+///
+/// This never collects unless a collection threshold is set. Every managed object that is still
+/// in use must be rooted.
+pub fn collect_if_needed() {
+    collect_if_needed_with_allocator::<Global>()
+}
+
+/// Collect garbage if the heap and external memory have grown enough since the last collection
+///
+/// This never collects unless a collection threshold is set. Every managed object that is still
+/// in use must be rooted.
+pub fn collect_if_needed_with_allocator<A: Allocator + 'static>() {
+    if with_gc(|gc: Pin<&GcState<A>>| gc.should_collect()) {
+        collect_with_allocator::<A>()
+    }
+}
+
+/// Collect garbage, returning the panics raised by finalizers
+pub fn try_collect() -> Result<(), FinalizerPanics> {
+    try_collect_with_allocator::<Global>()
+}
+
+/// Collect garbage, returning the panics raised by finalizers
+pub fn try_collect_with_allocator<A: Allocator + 'static>() -> Result<(), FinalizerPanics> {
+    FinalizerPanics::check(with_gc(|gc: Pin<&GcState<A>>| gc.collect()))
 }
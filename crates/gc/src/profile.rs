@@ -0,0 +1,12 @@
+use std::panic::Location;
+
+/// The live objects allocated at one source location
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AllocationSite {
+    /// Where the objects were allocated
+    pub location: &'static Location<'static>,
+    /// Number of managed objects allocated there
+    pub objects: usize,
+    /// Total size in bytes of those objects
+    pub bytes: usize,
+}
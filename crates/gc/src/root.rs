@@ -46,3 +46,45 @@ fn drop(&mut self) {
         super::pop_root::<A>(self.idx);
     }
 }
+
+/// A root slot holding any number of objects, released together
+pub struct RootSegment<A: Allocator + 'static = Global> {
+    idx: usize,
+    _phantom: PhantomData<*mut A>,
+}
+
+impl RootSegment {
+    pub fn new() -> RootSegment {
+        RootSegment::with_allocator()
+    }
+}
+
+impl Default for RootSegment {
+    fn default() -> RootSegment {
+        RootSegment::new()
+    }
+}
+
+impl<A: Allocator + 'static> RootSegment<A> {
+    pub fn with_allocator() -> RootSegment<A> {
+        RootSegment {
+            idx: super::new_root_segment::<A>(),
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Root `gc_ptr` until the segment is dropped
+    ///
+    /// # Safety
+    ///
+    /// `gc_ptr` must not be dangling
+    pub unsafe fn enroot<T: Trace + ?Sized>(&self, gc_ptr: GcPtr<T, A>) {
+        super::push_root(self.idx, gc_ptr)
+    }
+}
+
+impl<A: Allocator + 'static> Drop for RootSegment<A> {
+    fn drop(&mut self) {
+        super::pop_root::<A>(self.idx);
+    }
+}
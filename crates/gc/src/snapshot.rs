@@ -0,0 +1,310 @@
+use std::alloc::{Allocator, Global};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::pin::Pin;
+
+use std::ptr::NonNull;
+
+use crate::state::GcState;
+use crate::trace::Visitor;
+
+/// The identity, type and size of a managed object
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectInfo {
+    /// Serial number of the object, never reused by the same heap
+    pub id: u64,
+    /// Address of the allocation, which may be reused once the object is freed
+    pub address: usize,
+    /// Name of the type of the object
+    pub type_name: &'static str,
+    /// Size in bytes of the allocation, header included
+    pub size: usize,
+}
+
+/// Where a root is held
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RootId {
+    /// A slot of the root stack, by index
+    Stack(usize),
+    /// An entry of the global registry, by name
+    Global(&'static str),
+}
+
+impl fmt::Display for RootId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RootId::Stack(slot) => write!(f, "root #{}", slot),
+            RootId::Global(name) => write!(f, "global {:?}", name),
+        }
+    }
+}
+
+/// The chain of references keeping an object reachable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetainingPath {
+    /// The root the chain starts from
+    pub root: RootId,
+    /// The objects from the rooted one to the retained one, both included
+    pub objects: Vec<ObjectInfo>,
+}
+
+impl fmt::Display for RetainingPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.root)?;
+        for object in &self.objects {
+            write!(f, " -> {} #{}", object.type_name, object.id)?;
+        }
+        Ok(())
+    }
+}
+
+/// How an object was first reached while searching for retaining paths
+enum Parent {
+    Root(RootId),
+    Object(usize),
+}
+
+/// A record of every managed object, the references between them and the roots
+#[derive(Clone, Debug)]
+pub struct HeapSnapshot {
+    objects: Vec<ObjectInfo>,
+    edges: Vec<Vec<usize>>,
+    fields: Vec<Vec<Option<&'static str>>>,
+    roots: Vec<(RootId, usize)>,
+}
+
+impl HeapSnapshot {
+    pub(crate) fn new<I>(objects: I, roots: Vec<(RootId, *const ())>) -> HeapSnapshot
+    where
+        I: Iterator<Item = (*const (), ObjectInfo, Vec<Edge>)>,
+    {
+        let mut addresses = Vec::new();
+        let mut infos = Vec::new();
+        let mut targets = Vec::new();
+        for (address, info, edges) in objects {
+            addresses.push(address);
+            infos.push(info);
+            targets.push(edges);
+        }
+
+        let index: HashMap<*const (), usize> = addresses
+            .into_iter()
+            .enumerate()
+            .map(|(idx, address)| (address, idx))
+            .collect();
+        let (edges, fields) = targets
+            .into_iter()
+            .map(|edges| {
+                edges
+                    .iter()
+                    .filter_map(|edge| Some((*index.get(&edge.target)?, edge.field)))
+                    .unzip()
+            })
+            .unzip();
+        let roots = roots
+            .into_iter()
+            .filter_map(|(id, root)| Some((id, *index.get(&root)?)))
+            .collect();
+
+        HeapSnapshot {
+            objects: infos,
+            edges,
+            fields,
+            roots,
+        }
+    }
+
+    /// Record the current state of the heap
+    pub fn capture() -> HeapSnapshot {
+        HeapSnapshot::capture_with_allocator::<Global>()
+    }
+
+    /// Record the current state of the heap
+    pub fn capture_with_allocator<A: Allocator + 'static>() -> HeapSnapshot {
+        crate::with_gc(|gc: Pin<&GcState<A>>| gc.snapshot())
+    }
+
+    /// Every managed object at the time of the snapshot
+    pub fn objects(&self) -> &[ObjectInfo] {
+        &self.objects
+    }
+
+    /// The indices, into `objects`, of the objects referenced by the object at `idx`
+    pub fn references(&self, idx: usize) -> &[usize] {
+        &self.edges[idx]
+    }
+
+    /// The field holding each reference of the object at `idx`, in the order of `references`
+    ///
+    /// Fields are only named by types that report them to the visitor, such as derived ones.
+    pub fn fields(&self, idx: usize) -> &[Option<&'static str>] {
+        &self.fields[idx]
+    }
+
+    /// The occupied stack slots then the globals, each with the index of the rooted object
+    pub fn roots(&self) -> &[(RootId, usize)] {
+        &self.roots
+    }
+
+    /// Find the shortest retaining path of the object at `idx`, `None` if it is unreachable
+    pub fn retaining_path(&self, idx: usize) -> Option<RetainingPath> {
+        self.path(&self.parents(), idx)
+    }
+
+    /// Find the shortest retaining path of every object, `None` for unreachable ones
+    pub fn retaining_paths(&self) -> Vec<Option<RetainingPath>> {
+        let parents = self.parents();
+        (0..self.objects.len())
+            .map(|idx| self.path(&parents, idx))
+            .collect()
+    }
+
+    /// Search the heap breadth-first from the roots, recording how each object was reached
+    fn parents(&self) -> Vec<Option<Parent>> {
+        let mut parents: Vec<Option<Parent>> = self.objects.iter().map(|_| None).collect();
+        let mut queue = VecDeque::new();
+        for &(id, idx) in &self.roots {
+            if parents[idx].is_none() {
+                parents[idx] = Some(Parent::Root(id));
+                queue.push_back(idx);
+            }
+        }
+        while let Some(idx) = queue.pop_front() {
+            for &target in &self.edges[idx] {
+                if parents[target].is_none() {
+                    parents[target] = Some(Parent::Object(idx));
+                    queue.push_back(target);
+                }
+            }
+        }
+        parents
+    }
+
+    fn path(&self, parents: &[Option<Parent>], idx: usize) -> Option<RetainingPath> {
+        let mut objects = vec![self.objects[idx]];
+        let mut current = idx;
+        loop {
+            match parents[current].as_ref()? {
+                Parent::Root(root) => {
+                    objects.reverse();
+                    return Some(RetainingPath {
+                        root: *root,
+                        objects,
+                    });
+                }
+                Parent::Object(parent) => {
+                    current = *parent;
+                    objects.push(self.objects[current]);
+                }
+            }
+        }
+    }
+
+    /// Find the objects of `after` that are not in `before`, grouped by type
+    pub fn diff(before: &HeapSnapshot, after: &HeapSnapshot) -> HeapDiff {
+        let existing: HashSet<u64> = before.objects.iter().map(|object| object.id).collect();
+        let mut by_type: BTreeMap<&'static str, Vec<LeakedObject>> = BTreeMap::new();
+        for (object, path) in after.objects.iter().zip(after.retaining_paths()) {
+            if !existing.contains(&object.id) {
+                by_type
+                    .entry(object.type_name)
+                    .or_default()
+                    .push(LeakedObject {
+                        object: *object,
+                        path,
+                    });
+            }
+        }
+        HeapDiff { by_type }
+    }
+}
+
+/// A reference to another object, labelled with the innermost field holding it
+pub(crate) struct Edge {
+    pub target: *const (),
+    pub field: Option<&'static str>,
+}
+
+/// Collects the edges of an object along with their field names
+#[derive(Default)]
+pub(crate) struct EdgeRecorder {
+    fields: Vec<&'static str>,
+    pub edges: Vec<Edge>,
+}
+
+impl Visitor for EdgeRecorder {
+    fn edge(&mut self, target: NonNull<()>) {
+        self.edges.push(Edge {
+            target: target.as_ptr() as *const (),
+            field: self.fields.last().copied(),
+        });
+    }
+
+    fn enter_field(&mut self, name: &'static str) {
+        self.fields.push(name);
+    }
+
+    fn leave_field(&mut self) {
+        self.fields.pop();
+    }
+}
+
+/// An object found only in the later of two snapshots
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeakedObject {
+    pub object: ObjectInfo,
+    /// How the object is reachable, `None` if it is garbage that was not collected yet
+    pub path: Option<RetainingPath>,
+}
+
+/// The objects that appeared between two snapshots, grouped by type
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapDiff {
+    by_type: BTreeMap<&'static str, Vec<LeakedObject>>,
+}
+
+impl HeapDiff {
+    /// Tell if no object appeared
+    pub fn is_empty(&self) -> bool {
+        self.by_type.is_empty()
+    }
+
+    /// Count the objects that appeared
+    pub fn len(&self) -> usize {
+        self.by_type.values().map(Vec::len).sum()
+    }
+
+    /// The new objects, grouped by type name
+    pub fn by_type(&self) -> &BTreeMap<&'static str, Vec<LeakedObject>> {
+        &self.by_type
+    }
+
+    /// The new objects of the given type
+    pub fn objects_of(&self, type_name: &str) -> &[LeakedObject] {
+        self.by_type
+            .get(type_name)
+            .map_or(&[], |objects| &objects[..])
+    }
+}
+
+impl fmt::Display for HeapDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (type_name, objects) in &self.by_type {
+            let bytes: usize = objects.iter().map(|leaked| leaked.object.size).sum();
+            writeln!(
+                f,
+                "{}: {} object(s), {} bytes",
+                type_name,
+                objects.len(),
+                bytes
+            )?;
+            for leaked in objects {
+                match &leaked.path {
+                    Some(path) => writeln!(f, "    #{} via {}", leaked.object.id, path)?,
+                    None => writeln!(f, "    #{} (unreachable)", leaked.object.id)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
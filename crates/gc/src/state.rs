@@ -1,61 +1,392 @@
 use std::alloc::{Allocator, Global};
-use std::cell::{Ref, RefCell};
+use std::any::Any;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::BTreeMap;
+#[cfg(feature = "allocation-profile")]
+use std::collections::HashMap;
+use std::mem;
+#[cfg(feature = "allocation-profile")]
+use std::panic::Location;
 use std::pin::Pin;
 use std::ptr::NonNull;
+use std::time::Instant;
 
 use log::*;
 
-use crate::alloc::{Allocation, Data};
+use crate::alloc::{Allocation, Data, GcAllocError};
+use crate::gc_cell;
 use crate::gc_ptr::GcPtr;
+use crate::hooks::{GcEvent, HookId, Hooks};
 use crate::list::List;
+#[cfg(feature = "allocation-profile")]
+use crate::profile::AllocationSite;
+use crate::snapshot::{EdgeRecorder, HeapSnapshot, ObjectInfo, RetainingPath, RootId};
+use crate::stats::GcStats;
 use crate::trace::Trace;
 
 pub struct GcState<A: Allocator = Global> {
     objects: List<Allocation<Data, A>>,
-    roots: RefCell<Vec<Option<NonNull<Allocation<Data, A>>>>>,
+    roots: RefCell<Vec<RootSlot<A>>>,
+    globals: RefCell<BTreeMap<&'static str, GlobalRoot<A>>>,
+    heap_size: Cell<usize>,
+    max_heap_size: Cell<Option<usize>>,
+    memory_pressure: Cell<usize>,
+    /// The memory use below which no automatic collection happens, `None` if they are disabled
+    min_threshold: Cell<Option<usize>>,
+    /// The memory use that triggers the next automatic collection, twice that after the last one
+    threshold: Cell<usize>,
+    object_count: Cell<usize>,
+    next_id: Cell<u64>,
+    collecting: Cell<bool>,
+    /// Set between the mark and sweep phases, when objects managed or rooted by hooks must be
+    /// marked to survive the sweep
+    sweep_pending: Cell<bool>,
+    stats: Cell<GcStats>,
+    hooks: Hooks,
+}
+
+/// An entry of the root stack
+pub enum RootSlot<A: Allocator> {
+    /// A slot rooting at most one object
+    Single(Option<NonNull<Allocation<Data, A>>>),
+    /// A growable segment rooting any number of objects, popped as a unit
+    Segment(Vec<NonNull<Allocation<Data, A>>>),
+    /// A slot released before the slots above it, reclaimed once they are released too
+    Free,
+}
+
+impl<A: Allocator> RootSlot<A> {
+    /// The objects rooted by this slot
+    pub fn as_slice(&self) -> &[NonNull<Allocation<Data, A>>] {
+        match self {
+            RootSlot::Single(root) => root.as_slice(),
+            RootSlot::Segment(roots) => roots,
+            RootSlot::Free => &[],
+        }
+    }
+}
+
+/// An entry of the global registry
+struct GlobalRoot<A: Allocator> {
+    root: NonNull<Allocation<Data, A>>,
+    /// The typed `GcPtr`, checked when the global is looked up
+    ptr: Box<dyn Any>,
+}
+
+/// Ends the running collection when dropped, even if it is interrupted by a panic
+struct Collecting<'a, A: Allocator> {
+    gc: Pin<&'a GcState<A>>,
+}
+
+impl<A: Allocator> Drop for Collecting<'_, A> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            // Clear the marks of an interrupted mark phase, or they would keep garbage alive
+            for object in self.gc.objects() {
+                object.marked();
+            }
+        }
+        self.gc.sweep_pending.set(false);
+        self.gc.collecting.set(false);
+    }
 }
 
 impl<A: Allocator> GcState<A> {
-    pub fn collect(self: Pin<&Self>) {
-        for (idx, root) in self.roots()[..].iter().enumerate() {
-            if let Some(root) = root {
+    /// Run a full collection, returning the panics raised by finalizers and hooks
+    ///
+    /// Calling this while a collection is already running, e.g. from a finalizer, or while a
+    /// `GcCell` is mutably borrowed does nothing.
+    pub fn collect(self: Pin<&Self>) -> Vec<Box<dyn Any + Send>> {
+        if gc_cell::collection_deferred() {
+            debug!("SKIPPING collection while a GcCell is mutably borrowed");
+            return Vec::new();
+        }
+        if self.collecting.replace(true) {
+            debug!("SKIPPING nested collection");
+            return Vec::new();
+        }
+        let collecting = Collecting { gc: self };
+        let start = Instant::now();
+        let mut panics = Vec::new();
+
+        self.update_stats(|stats| stats.collections += 1);
+        #[cfg(feature = "tracing")]
+        let collect_span = tracing::debug_span!(
+            "collect",
+            collection = self.stats.get().collections,
+            objects_marked = tracing::field::Empty,
+            objects_freed = tracing::field::Empty,
+            bytes_freed = tracing::field::Empty,
+            pause_us = tracing::field::Empty,
+        )
+        .entered();
+        panics.extend(self.fire(GcEvent::BeforeMark));
+
+        let objects_marked = {
+            #[cfg(feature = "tracing")]
+            let mark_span =
+                tracing::debug_span!("mark", objects_marked = tracing::field::Empty).entered();
+
+            for (idx, slot) in self.roots().iter().enumerate() {
+                for root in slot.as_slice() {
+                    debug!(
+                        "TRACING from root at:       {:x} (idx {:x})",
+                        &*root as *const _ as usize, idx
+                    );
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(object = root.as_ptr() as usize, idx, "tracing from root");
+                    unsafe {
+                        root.as_ref().mark();
+                    }
+                }
+            }
+            for (name, global) in self.globals.borrow().iter() {
                 debug!(
-                    "TRACING from root at:       {:x} (idx {:x})",
-                    &*root as *const _ as usize, idx
+                    "TRACING from global at:     {:x} ({})",
+                    global.root.as_ptr() as usize,
+                    name
                 );
                 unsafe {
-                    root.as_ref().mark();
+                    global.root.as_ref().mark();
                 }
             }
-        }
 
-        for object in self.objects() {
-            if !object.marked() {
-                debug!(
-                    "FREEING unmarked object at: {:x}",
-                    &*object as *const _ as usize
-                );
-                unsafe {
-                    Allocation::free(
-                        &*object as *const Allocation<Data, A> as *mut Allocation<Data, A>,
-                    )
+            let objects_marked = self.objects().into_iter().filter(|o| o.is_marked()).count();
+            #[cfg(feature = "tracing")]
+            mark_span.record("objects_marked", objects_marked);
+            objects_marked
+        };
+        self.update_stats(|stats| stats.objects_marked = objects_marked);
+        self.sweep_pending.set(true);
+        panics.extend(self.fire(GcEvent::AfterMark));
+        panics.extend(self.fire(GcEvent::BeforeSweep));
+        self.sweep_pending.set(false);
+
+        let mut objects_freed = 0;
+        let mut bytes_freed = 0;
+        {
+            #[cfg(feature = "tracing")]
+            let sweep_span = tracing::debug_span!(
+                "sweep",
+                objects_freed = tracing::field::Empty,
+                bytes_freed = tracing::field::Empty,
+            )
+            .entered();
+
+            for object in self.objects() {
+                if !object.marked() {
+                    debug!(
+                        "FREEING unmarked object at: {:x}",
+                        &*object as *const _ as usize
+                    );
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        object = &*object as *const _ as usize,
+                        size = object.size(),
+                        "freeing unmarked object"
+                    );
+                    objects_freed += 1;
+                    bytes_freed += object.size();
+                    self.heap_size.set(self.heap_size.get() - object.size());
+                    self.object_count.set(self.object_count.get() - 1);
+                    let freed = unsafe {
+                        Allocation::free(
+                            &*object as *const Allocation<Data, A> as *mut Allocation<Data, A>,
+                        )
+                    };
+                    if let Err(panic) = freed {
+                        debug!("PANICKED while finalizing object");
+                        #[cfg(feature = "tracing")]
+                        tracing::trace!("finalizer panicked");
+                        panics.push(panic);
+                    }
                 }
             }
+
+            #[cfg(feature = "tracing")]
+            {
+                sweep_span.record("objects_freed", objects_freed);
+                sweep_span.record("bytes_freed", bytes_freed);
+            }
+        }
+
+        self.threshold.set(self.memory_usage().saturating_mul(2));
+        drop(collecting);
+
+        let pause = start.elapsed();
+        self.update_stats(|stats| {
+            stats.objects_freed = objects_freed;
+            stats.bytes_freed = bytes_freed;
+            stats.last_pause = pause;
+        });
+        #[cfg(feature = "tracing")]
+        {
+            collect_span.record("objects_marked", objects_marked);
+            collect_span.record("objects_freed", objects_freed);
+            collect_span.record("bytes_freed", bytes_freed);
+            collect_span.record("pause_us", pause.as_micros() as u64);
+        }
+        panics.extend(self.fire(GcEvent::AfterCollect));
+        panics
+    }
+
+    /// The current statistics of this heap
+    pub fn stats(self: Pin<&Self>) -> GcStats {
+        GcStats {
+            managed_objects: self.object_count.get(),
+            heap_size: self.heap_size.get(),
+            external_size: self.external_size(),
+            ..self.stats.get()
         }
     }
 
+    /// Group the managed objects by allocation site, largest first
+    #[cfg(feature = "allocation-profile")]
+    pub fn allocation_profile(self: Pin<&Self>) -> Vec<AllocationSite> {
+        let mut sites: HashMap<&'static Location<'static>, AllocationSite> = HashMap::new();
+        for object in self.objects() {
+            let site = sites
+                .entry(object.location())
+                .or_insert_with(|| AllocationSite {
+                    location: object.location(),
+                    objects: 0,
+                    bytes: 0,
+                });
+            site.objects += 1;
+            site.bytes += object.size();
+        }
+
+        let mut sites: Vec<AllocationSite> = sites.into_values().collect();
+        sites.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.objects.cmp(&a.objects)));
+        sites
+    }
+
+    /// Record every managed object, its outgoing edges and the roots
+    pub fn snapshot(self: Pin<&Self>) -> HeapSnapshot {
+        let objects: Vec<Pin<&Allocation<Data, A>>> = self.objects().into_iter().collect();
+        let roots = self.root_ids();
+        HeapSnapshot::new(
+            objects.iter().map(|object| {
+                let mut recorder = EdgeRecorder::default();
+                object.visit(&mut recorder);
+                (
+                    &**object as *const _ as *const (),
+                    object.info(),
+                    recorder.edges,
+                )
+            }),
+            roots,
+        )
+    }
+
+    /// Every rooted object, stack slots first then globals
+    fn root_ids(&self) -> Vec<(RootId, *const ())> {
+        let roots = self.roots();
+        let stack = roots.iter().enumerate().flat_map(|(idx, slot)| {
+            slot.as_slice()
+                .iter()
+                .map(move |root| (RootId::Stack(idx), root.as_ptr() as *const ()))
+        });
+        let globals = self.globals.borrow();
+        let globals = globals
+            .iter()
+            .map(|(&name, global)| (RootId::Global(name), global.root.as_ptr() as *const ()));
+        stack.chain(globals).collect()
+    }
+
+    /// Find the shortest chain of references from a root to `target`
+    pub fn retaining_path(self: Pin<&Self>, target: *const ()) -> Option<RetainingPath> {
+        let snapshot = self.snapshot();
+        let idx = snapshot
+            .objects()
+            .iter()
+            .position(|object| object.address == target as usize)?;
+        snapshot.retaining_path(idx)
+    }
+
+    pub fn add_hook<F: FnMut(&GcStats) + 'static>(&self, event: GcEvent, hook: F) -> HookId {
+        self.hooks.add(event, hook)
+    }
+
+    pub fn remove_hook(&self, id: HookId) -> bool {
+        self.hooks.remove(id)
+    }
+
+    fn fire(self: Pin<&Self>, event: GcEvent) -> Vec<Box<dyn Any + Send>> {
+        self.hooks.fire(event, &self.stats())
+    }
+
+    fn update_stats(&self, f: impl FnOnce(&mut GcStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    /// Tell if memory use has grown enough since the last collection to warrant another one
+    ///
+    /// Always false unless a collection threshold is set. The external size of every object is
+    /// summed anew, as it may have changed since the object was allocated.
+    pub fn should_collect(self: Pin<&Self>) -> bool {
+        match self.min_threshold.get() {
+            Some(min_threshold) => self.memory_usage() >= self.threshold.get().max(min_threshold),
+            None => false,
+        }
+    }
+
+    pub fn collection_threshold(&self) -> Option<usize> {
+        self.min_threshold.get()
+    }
+
+    pub fn set_collection_threshold(&self, threshold: Option<usize>) {
+        self.min_threshold.set(threshold)
+    }
+
     pub unsafe fn manage<T: Trace + ?Sized>(self: Pin<&Self>, ptr: GcPtr<T, A>) {
         // TODO I should not need a dynamic check here but I am making mistakes
         if ptr.is_unmanaged() {
-            self.objects().insert(ptr.erased_pinned());
+            let object = ptr.erased_pinned();
+            object.set_id(self.next_id.replace(self.next_id.get() + 1));
+            self.objects().insert(object);
+            self.heap_size.set(self.heap_size.get() + object.size());
+            self.object_count.set(self.object_count.get() + 1);
+
+            // The pointers of an already managed object were managed along with it, so stopping
+            // here also ends the recursion on cycles
+            if object.is_initialized() {
+                ptr.data().manage();
+            }
+            self.mark_if_sweep_pending(ptr);
         }
+    }
+
+    /// Initialize an object allocated by `alloc_uninit`, managing the pointers it holds
+    pub unsafe fn write<T: Trace>(self: Pin<&Self>, ptr: GcPtr<T, A>, data: T) {
+        ptr.write(data);
         ptr.data().manage();
+        if self.sweep_pending.get() {
+            // The object was marked before it held anything
+            ptr.data().mark();
+        }
+    }
+
+    /// Keep an object managed or rooted by a hook alive through the coming sweep
+    fn mark_if_sweep_pending<T: Trace + ?Sized>(self: Pin<&Self>, ptr: GcPtr<T, A>) {
+        if self.sweep_pending.get() {
+            unsafe { ptr.erased_pinned().mark() }
+        }
     }
 
     pub fn new_root(self: Pin<&Self>) -> usize {
         let mut roots = self.roots.borrow_mut();
         let ret = roots.len();
-        roots.push(None);
+        roots.push(RootSlot::Single(None));
+        ret
+    }
+
+    pub fn new_root_segment(self: Pin<&Self>) -> usize {
+        let mut roots = self.roots.borrow_mut();
+        let ret = roots.len();
+        roots.push(RootSlot::Segment(Vec::new()));
         ret
     }
 
@@ -66,21 +397,142 @@ pub fn set_root<T: Trace + ?Sized>(self: Pin<&Self>, idx: usize, ptr: GcPtr<T, A
             root.as_ptr() as usize,
             idx
         );
-        self.roots.borrow_mut()[idx] = Some(root);
+        self.mark_if_sweep_pending(ptr);
+        match &mut self.roots.borrow_mut()[idx] {
+            RootSlot::Single(slot) => *slot = Some(root),
+            RootSlot::Segment(_) => panic!("root {} is a segment", idx),
+            RootSlot::Free => panic!("root {} was released", idx),
+        }
+    }
+
+    pub fn push_root<T: Trace + ?Sized>(self: Pin<&Self>, idx: usize, ptr: GcPtr<T, A>) {
+        let root: NonNull<Allocation<Data, A>> = ptr.erased();
+        debug!(
+            "ENROOTING root at: {:x} (segment {:x})",
+            root.as_ptr() as usize,
+            idx
+        );
+        self.mark_if_sweep_pending(ptr);
+        match &mut self.roots.borrow_mut()[idx] {
+            RootSlot::Segment(segment) => segment.push(root),
+            RootSlot::Single(_) | RootSlot::Free => panic!("root {} is not a segment", idx),
+        }
+    }
+
+    /// Root `ptr` under `name` until it is unregistered, returning whether a global was replaced
+    pub fn register_global<T: Trace + ?Sized + 'static>(
+        self: Pin<&Self>,
+        name: &'static str,
+        ptr: GcPtr<T, A>,
+    ) -> bool
+    where
+        A: 'static,
+    {
+        self.mark_if_sweep_pending(ptr);
+        let global = GlobalRoot {
+            root: ptr.erased(),
+            ptr: Box::new(ptr),
+        };
+        self.globals.borrow_mut().insert(name, global).is_some()
     }
 
+    /// Look up the global `name`, `None` if it is missing or of another type
+    pub fn global<T: Trace + ?Sized + 'static>(self: Pin<&Self>, name: &str) -> Option<GcPtr<T, A>>
+    where
+        A: 'static,
+    {
+        let globals = self.globals.borrow();
+        globals.get(name)?.ptr.downcast_ref().copied()
+    }
+
+    /// Stop rooting the global `name`, returning whether it was registered
+    pub fn unregister_global(self: Pin<&Self>, name: &str) -> bool {
+        self.globals.borrow_mut().remove(name).is_some()
+    }
+
+    /// The registered globals, by name
+    pub fn globals(self: Pin<&Self>) -> Vec<(&'static str, ObjectInfo)> {
+        self.globals
+            .borrow()
+            .iter()
+            .map(|(name, global)| (*name, unsafe { global.root.as_ref().info() }))
+            .collect()
+    }
+
+    /// Release the slot `idx`, which need not be the last one
     pub fn pop_root(self: Pin<&Self>, idx: usize) {
-        debug_assert!(idx + 1 == self.roots.borrow().len());
-        if let Some(root) = self.roots.borrow_mut().pop().unwrap() {
+        let mut roots = self.roots.borrow_mut();
+        let slot = mem::replace(&mut roots[idx], RootSlot::Free);
+        for root in slot.as_slice() {
             debug!(
                 "DROPPING root at: {:x} (idx {:x})",
                 root.as_ptr() as usize,
                 idx
             );
         }
+        while let Some(RootSlot::Free) = roots.last() {
+            roots.pop();
+        }
+    }
+
+    /// Total size in bytes of the managed objects
+    pub fn heap_size(&self) -> usize {
+        self.heap_size.get()
+    }
+
+    pub fn max_heap_size(&self) -> Option<usize> {
+        self.max_heap_size.get()
+    }
+
+    pub fn set_max_heap_size(&self, max_heap_size: Option<usize>) {
+        self.max_heap_size.set(max_heap_size)
+    }
+
+    /// Bytes owned by managed objects outside of the heap, including manual memory pressure
+    pub fn external_size(self: Pin<&Self>) -> usize {
+        self.objects()
+            .into_iter()
+            .map(|object| object.external_size())
+            .fold(self.memory_pressure.get(), usize::saturating_add)
+    }
+
+    pub fn add_memory_pressure(&self, bytes: usize) {
+        self.memory_pressure
+            .set(self.memory_pressure.get().saturating_add(bytes))
+    }
+
+    pub fn remove_memory_pressure(&self, bytes: usize) {
+        self.memory_pressure
+            .set(self.memory_pressure.get().saturating_sub(bytes))
+    }
+
+    fn memory_usage(self: Pin<&Self>) -> usize {
+        self.heap_size.get().saturating_add(self.external_size())
+    }
+
+    /// Tell if `bytes` more can be managed without going over the maximum heap size
+    pub fn check_heap_limit(&self, bytes: usize) -> Result<(), GcAllocError> {
+        match self.max_heap_size.get() {
+            Some(max_heap_size) if self.heap_size.get() + bytes > max_heap_size => {
+                Err(GcAllocError::HeapLimitExceeded {
+                    requested: bytes,
+                    heap_size: self.heap_size.get(),
+                    max_heap_size,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The slots in use, leaving out the released ones that cannot be reclaimed yet
+    pub fn count_roots(&self) -> usize {
+        self.roots()
+            .iter()
+            .filter(|slot| !matches!(slot, RootSlot::Free))
+            .count()
     }
 
-    pub fn roots(&self) -> Ref<'_, [Option<NonNull<Allocation<Data, A>>>]> {
+    pub fn roots(&self) -> Ref<'_, [RootSlot<A>]> {
         Ref::map(self.roots.borrow(), |v| &v[..])
     }
 
@@ -94,6 +546,18 @@ fn default() -> Self {
         Self {
             objects: Default::default(),
             roots: Default::default(),
+            globals: Default::default(),
+            heap_size: Default::default(),
+            max_heap_size: Default::default(),
+            memory_pressure: Default::default(),
+            min_threshold: Default::default(),
+            threshold: Default::default(),
+            object_count: Default::default(),
+            next_id: Cell::new(1),
+            collecting: Default::default(),
+            sweep_pending: Default::default(),
+            stats: Default::default(),
+            hooks: Default::default(),
         }
     }
 }
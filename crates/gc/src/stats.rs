@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+/// A snapshot of the state of a heap, as seen by collection hooks
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// Number of collections started so far, including the one in progress
+    pub collections: usize,
+    /// Number of objects managed by the GC
+    pub managed_objects: usize,
+    /// Total size in bytes of the managed objects
+    pub heap_size: usize,
+    /// Bytes owned by managed objects outside of the heap, including manual memory pressure
+    pub external_size: usize,
+    /// Number of objects found reachable by the latest mark phase
+    pub objects_marked: usize,
+    /// Number of objects freed by the latest sweep
+    pub objects_freed: usize,
+    /// Number of heap bytes released by the latest sweep
+    pub bytes_freed: usize,
+    /// How long the latest collection took from start to finish
+    pub last_pause: Duration,
+}
@@ -1,17 +1,56 @@
 use pin_cell::PinCell;
+use std::any;
 use std::cell::{Cell, RefCell};
 use std::collections::*;
 use std::mem::{self, ManuallyDrop};
-use std::ptr;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
 
 pub unsafe trait Trace {
     unsafe fn mark(&self);
     unsafe fn manage(&self);
     unsafe fn finalize(&mut self);
+
+    /// Report every GC pointer held directly by this value to `visitor`
+    ///
+    /// Values without GC pointers have nothing to report. Edges that are not reported are
+    /// missing from heap snapshots and retaining paths, but collection does not rely on them.
+    fn visit(&self, visitor: &mut dyn Visitor);
+
+    /// The name of the concrete type, as recorded in heap snapshots
+    fn type_name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+
+    /// Bytes owned outside of the GC heap, such as the buffer of a `Vec`
+    ///
+    /// The collector adds this to the heap size when deciding when to collect. It does not need
+    /// to be exact, but should not count other GC objects.
+    fn external_size(&self) -> usize {
+        0
+    }
 }
 
 pub unsafe trait NullTrace: Trace {}
 
+/// Receives the edges of the object graph
+pub trait Visitor {
+    /// Called with the allocation referenced by a GC pointer
+    fn edge(&mut self, target: NonNull<()>);
+
+    /// Called before visiting the field `name`, so that edges can be labelled in heap snapshots
+    fn enter_field(&mut self, _name: &'static str) {}
+
+    /// Called after visiting the field last entered
+    fn leave_field(&mut self) {}
+}
+
+impl<F: FnMut(NonNull<()>)> Visitor for F {
+    fn edge(&mut self, target: NonNull<()>) {
+        self(target)
+    }
+}
+
 unsafe impl<T: Trace> Trace for Option<T> {
     unsafe fn mark(&self) {
         if let Some(inner) = self {
@@ -29,6 +68,16 @@ unsafe fn finalize(&mut self) {
             inner.finalize()
         }
     }
+
+    fn external_size(&self) -> usize {
+        self.as_ref().map_or(0, T::external_size)
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        if let Some(inner) = self {
+            inner.visit(visitor)
+        }
+    }
 }
 
 unsafe impl<T: NullTrace> NullTrace for Option<T> {}
@@ -52,6 +101,20 @@ unsafe fn finalize(&mut self) {
             Err(error) => error.finalize(),
         }
     }
+
+    fn external_size(&self) -> usize {
+        match self {
+            Ok(inner) => inner.external_size(),
+            Err(error) => error.external_size(),
+        }
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        match self {
+            Ok(inner) => inner.visit(visitor),
+            Err(error) => error.visit(visitor),
+        }
+    }
 }
 
 unsafe impl<T: NullTrace, E: NullTrace> NullTrace for Result<T, E> {}
@@ -72,6 +135,16 @@ unsafe fn finalize(&mut self) {
             elem.finalize()
         }
     }
+
+    fn external_size(&self) -> usize {
+        self.iter().map(T::external_size).sum()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T: NullTrace> NullTrace for [T] {}
@@ -86,6 +159,14 @@ unsafe fn manage(&self) {
     unsafe fn finalize(&mut self) {
         <_ as AsMut<[T]>>::as_mut(self).finalize()
     }
+
+    fn external_size(&self) -> usize {
+        <_ as AsRef<[T]>>::as_ref(self).external_size()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        <_ as AsRef<[T]>>::as_ref(self).visit(visitor)
+    }
 }
 unsafe impl<T: NullTrace, const N: usize> NullTrace for [T; N] {}
 
@@ -97,6 +178,7 @@ unsafe fn manage(&self) { }
             unsafe fn finalize(&mut self) {
                 ptr::drop_in_place(self as *mut Self)
             }
+            fn visit(&self, _: &mut dyn Visitor) { }
         }
         unsafe impl NullTrace for $t { }
     )*}
@@ -107,7 +189,7 @@ unsafe impl NullTrace for $t { }
     u8  u16 u32 u64 usize
     f32     f64
     char    bool
-    str     String
+    str
     std::fs::File
     std::fs::FileType
     std::fs::Metadata
@@ -127,7 +209,6 @@ unsafe impl NullTrace for $t { }
     std::net::SocketAddrV4
     std::net::SocketAddrV6
     std::path::Path
-    std::path::PathBuf
     std::process::Command
     std::process::Child
     std::process::ChildStdout
@@ -141,6 +222,28 @@ unsafe impl NullTrace for $t { }
     std::sync::Once
 );
 
+macro_rules!
+    trace_buffer { ($($t:ty)*) => {$(
+        unsafe impl Trace for $t {
+            unsafe fn mark(&self) { }
+            unsafe fn manage(&self) { }
+            unsafe fn finalize(&mut self) {
+                ptr::drop_in_place(self as *mut Self)
+            }
+            fn visit(&self, _: &mut dyn Visitor) { }
+            fn external_size(&self) -> usize {
+                self.capacity()
+            }
+        }
+        unsafe impl NullTrace for $t { }
+    )*}
+}
+
+trace_buffer!(
+    String
+    std::path::PathBuf
+);
+
 macro_rules! trace_tuples {
     ($(($($T:ident : $N:tt),*))*) => {$(
         unsafe impl<$($T: Trace,)*> Trace for ($($T,)*) {
@@ -153,6 +256,13 @@ unsafe fn manage(&self) {
             unsafe fn finalize(&mut self) {
                 $(self.$N.finalize();)*
             }
+            #[allow(unused_variables)]
+            fn visit(&self, visitor: &mut dyn Visitor) {
+                $(self.$N.visit(visitor);)*
+            }
+            fn external_size(&self) -> usize {
+                0 $(+ self.$N.external_size())*
+            }
         }
         unsafe impl<$($T: NullTrace,)*> NullTrace for ($($T,)*) { }
     )*};
@@ -194,6 +304,16 @@ unsafe fn finalize(&mut self) {
         let this = mem::transmute::<&mut Vec<T>, &mut Vec<ManuallyDrop<T>>>(self);
         ptr::drop_in_place(this as *mut Vec<ManuallyDrop<T>>);
     }
+
+    fn external_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>() + self.iter().map(T::external_size).sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T: NullTrace> NullTrace for Vec<T> {}
@@ -218,6 +338,16 @@ unsafe fn finalize(&mut self) {
         let this = mem::transmute::<&mut VecDeque<T>, &mut VecDeque<ManuallyDrop<T>>>(self);
         ptr::drop_in_place(this as *mut VecDeque<ManuallyDrop<T>>);
     }
+
+    fn external_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>() + self.iter().map(T::external_size).sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T: NullTrace> NullTrace for VecDeque<T> {}
@@ -242,6 +372,16 @@ unsafe fn finalize(&mut self) {
         let this = mem::transmute::<&mut LinkedList<T>, &mut LinkedList<ManuallyDrop<T>>>(self);
         ptr::drop_in_place(this as *mut LinkedList<ManuallyDrop<T>>);
     }
+
+    fn external_size(&self) -> usize {
+        self.len() * mem::size_of::<T>() + self.iter().map(T::external_size).sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T: NullTrace> NullTrace for LinkedList<T> {}
@@ -266,6 +406,16 @@ unsafe fn finalize(&mut self) {
         );
         iter.for_each(|mut elem| elem.finalize());
     }
+
+    fn external_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>() + self.iter().map(T::external_size).sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T: NullTrace + Ord> NullTrace for BinaryHeap<T> {}
@@ -293,6 +443,16 @@ unsafe fn finalize(&mut self) {
             mem::transmute::<hash_set::IntoIter<T>, hash_set::IntoIter<ManuallyDrop<T>>>(iter);
         iter.for_each(|mut elem| elem.finalize());
     }
+
+    fn external_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>() + self.iter().map(T::external_size).sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T, S> NullTrace for HashSet<T, S>
@@ -333,6 +493,21 @@ unsafe fn finalize(&mut self) {
             value.finalize();
         });
     }
+
+    fn external_size(&self) -> usize {
+        self.capacity() * mem::size_of::<(K, V)>()
+            + self
+                .iter()
+                .map(|(key, value)| key.external_size() + value.external_size())
+                .sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for (key, value) in self {
+            key.visit(visitor);
+            value.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<K, V, S> NullTrace for HashMap<K, V, S>
@@ -365,6 +540,16 @@ unsafe fn finalize(&mut self) {
             mem::transmute::<btree_set::IntoIter<T>, btree_set::IntoIter<ManuallyDrop<T>>>(iter);
         iter.for_each(|mut elem| elem.finalize());
     }
+
+    fn external_size(&self) -> usize {
+        self.len() * mem::size_of::<T>() + self.iter().map(T::external_size).sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for elem in self {
+            elem.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<T> NullTrace for BTreeSet<T> where T: Eq + Ord + NullTrace {}
@@ -399,6 +584,21 @@ unsafe fn finalize(&mut self) {
             value.finalize();
         });
     }
+
+    fn external_size(&self) -> usize {
+        self.len() * mem::size_of::<(K, V)>()
+            + self
+                .iter()
+                .map(|(key, value)| key.external_size() + value.external_size())
+                .sum::<usize>()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        for (key, value) in self {
+            key.visit(visitor);
+            value.visit(visitor);
+        }
+    }
 }
 
 unsafe impl<K, V> NullTrace for BTreeMap<K, V>
@@ -408,21 +608,108 @@ unsafe impl<K, V> NullTrace for BTreeMap<K, V>
 {
 }
 
+unsafe impl<T: Trace> Trace for Box<T> {
+    unsafe fn mark(&self) {
+        (**self).mark()
+    }
+
+    unsafe fn manage(&self) {
+        (**self).manage()
+    }
+
+    unsafe fn finalize(&mut self) {
+        (**self).finalize();
+        let this = mem::transmute::<&mut Box<T>, &mut Box<ManuallyDrop<T>>>(self);
+        ptr::drop_in_place(this as *mut Box<ManuallyDrop<T>>);
+    }
+
+    fn external_size(&self) -> usize {
+        mem::size_of::<T>() + (**self).external_size()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        (**self).visit(visitor)
+    }
+}
+
+unsafe impl<T: NullTrace> NullTrace for Box<T> {}
+
+unsafe impl<T: Trace> Trace for Rc<T> {
+    unsafe fn mark(&self) {
+        (**self).mark()
+    }
+
+    unsafe fn manage(&self) {
+        (**self).manage()
+    }
+
+    /// The contents are finalized along with the last strong reference, and only released
+    /// otherwise
+    unsafe fn finalize(&mut self) {
+        if Rc::strong_count(self) == 1 {
+            // Weak references cannot be upgraded while the last strong one is being dropped
+            (*(Rc::as_ptr(self) as *mut T)).finalize();
+            let this = mem::transmute::<&mut Rc<T>, &mut Rc<ManuallyDrop<T>>>(self);
+            ptr::drop_in_place(this as *mut Rc<ManuallyDrop<T>>);
+        } else {
+            ptr::drop_in_place(self as *mut Self)
+        }
+    }
+
+    /// Shared contents are counted once per reference
+    fn external_size(&self) -> usize {
+        mem::size_of::<T>() + (**self).external_size()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        (**self).visit(visitor)
+    }
+}
+
+unsafe impl<T: NullTrace> NullTrace for Rc<T> {}
+
 unsafe impl<T: NullTrace> Trace for Cell<T> {
     unsafe fn mark(&self) {}
     unsafe fn manage(&self) {}
     unsafe fn finalize(&mut self) {
         ptr::drop_in_place(self as *mut Self)
     }
+
+    fn visit(&self, _: &mut dyn Visitor) {}
 }
 
 unsafe impl<T: NullTrace> NullTrace for Cell<T> {}
 
-unsafe impl<T: NullTrace> Trace for RefCell<T> {
-    unsafe fn mark(&self) {}
-    unsafe fn manage(&self) {}
+/// A `RefCell` has no write barrier, so the objects stored in it since the last collection are
+/// only managed when it is marked. It must not be mutably borrowed then, unlike a `GcCell`.
+unsafe impl<T: Trace> Trace for RefCell<T> {
+    unsafe fn mark(&self) {
+        let inner = self
+            .try_borrow()
+            .expect("RefCell holding GC pointers is mutably borrowed during a collection");
+        inner.manage();
+        inner.mark()
+    }
+
+    unsafe fn manage(&self) {
+        if let Ok(inner) = self.try_borrow() {
+            inner.manage()
+        }
+    }
+
     unsafe fn finalize(&mut self) {
-        ptr::drop_in_place(self as *mut Self)
+        self.get_mut().finalize()
+    }
+
+    fn external_size(&self) -> usize {
+        self.try_borrow().map_or(0, |inner| inner.external_size())
+    }
+
+    /// The contents are skipped while the cell is mutably borrowed
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        if let Ok(inner) = self.try_borrow() {
+            inner.visit(visitor)
+        }
     }
 }
 
@@ -438,6 +725,17 @@ unsafe fn manage(&self) {
     unsafe fn finalize(&mut self) {
         self.get_mut().finalize()
     }
+
+    fn external_size(&self) -> usize {
+        self.try_borrow().map_or(0, |inner| inner.external_size())
+    }
+
+    /// The contents are skipped while the cell is mutably borrowed
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        if let Ok(inner) = self.try_borrow() {
+            inner.visit(visitor)
+        }
+    }
 }
 
 unsafe impl<T: NullTrace> NullTrace for PinCell<T> {}
@@ -1,15 +1,24 @@
 #![feature(arbitrary_self_types)]
 
-use nocturne::{GcStore, GC};
+use nocturne::{GcCell, GcOnceCell, GcStore, GC};
 
 use pin_cell::PinCell;
 use std::cell::RefCell;
+use std::rc::Rc;
 
 #[derive(GC)]
 struct Foo<'root> {
     null: RefCell<Null>,
     #[gc]
     traced: PinCell<GcStore<'root, i32>>,
+    #[gc]
+    shared: GcCell<Option<GcStore<'root, i32>>>,
+    #[gc]
+    cached: GcOnceCell<GcStore<'root, i32>>,
+    #[gc]
+    list: RefCell<Vec<GcStore<'root, i32>>>,
+    #[gc]
+    counted: Rc<GcStore<'root, i32>>,
 }
 
 #[derive(GC)]
@@ -24,7 +33,20 @@ fn main() {
     let foo = root.gc(Foo {
         null: RefCell::new(Null::A(0)),
         traced: PinCell::new(GcStore::new(0)),
+        shared: GcCell::new(None),
+        cached: GcOnceCell::new(),
+        list: RefCell::new(Vec::new()),
+        counted: Rc::new(GcStore::new(3)),
     });
     *foo.null.borrow_mut() = Null::B(String::new());
+    *foo.shared.borrow_mut() = Some(GcStore::new(1));
+    foo.list.borrow_mut().push(GcStore::new(4));
+    assert!(foo.cached().get().is_none());
+    foo.cached.get_or_init(|| GcStore::new(2));
+    nocturne::collect();
     println!("{}", foo.traced().borrow());
+    println!("{}", foo.cached().get().unwrap());
+    println!("{:?}", foo.shared().borrow().get().map(|shared| *shared));
+    println!("{:?}", foo.list().borrow().get());
+    println!("{}", foo.counted());
 }
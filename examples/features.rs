@@ -1,6 +1,8 @@
 #![feature(arbitrary_self_types)]
 
-use nocturne::{Gc, GcStore};
+use std::collections::BTreeMap;
+
+use nocturne::{Gc, GcStore, GcVec};
 
 #[derive(nocturne::GC)]
 #[gc(finalize)]
@@ -11,6 +13,11 @@ struct Foo<'root> {
     vec: Vec<GcStore<'root, i32>>,
     #[gc]
     option: Option<GcStore<'root, i32>>,
+    #[gc]
+    growable: GcVec<'root, i32>,
+    #[gc]
+    table: BTreeMap<String, Option<GcStore<'root, i32>>>,
+    #[gc(project)]
     local: i32,
 }
 
@@ -20,6 +27,8 @@ fn new() -> Foo<'root> {
             item: GcStore::new(0),
             vec: vec![GcStore::new(1), GcStore::new(2), GcStore::new(3)],
             option: Some(GcStore::new(4)),
+            growable: GcVec::new(),
+            table: BTreeMap::from([(String::from("seven"), Some(GcStore::new(7)))]),
             local: 5,
         }
     }
@@ -34,6 +43,16 @@ fn print_nonlocal(self: Gc<'_, Self>) {
         if let Some(thing) = self.option() {
             println!("{}", thing);
         }
+
+        for elem in &self.growable().borrow() {
+            println!("{}", elem);
+        }
+
+        for (name, elem) in self.table() {
+            println!("{}: {:?}", name, elem);
+        }
+
+        println!("{}", self.local());
     }
 }
 
@@ -45,9 +64,10 @@ fn finalize(&mut self) {
 
 fn main() {
     {
-        nocturne::letroot!(root);
+        nocturne::letroot!(item, root);
 
         let foo = root.gc(Foo::new());
+        foo.growable.push(item.gc(6));
 
         nocturne::collect();
 
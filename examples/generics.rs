@@ -0,0 +1,60 @@
+#![feature(arbitrary_self_types)]
+
+use nocturne::raw::{Reroot, Trace};
+use nocturne::{letroot, GcStore, GcVec, GC};
+
+#[derive(GC)]
+struct Pair<'root, K, V> {
+    #[gc]
+    key: GcStore<'root, K>,
+    #[gc]
+    values: GcVec<'root, V>,
+}
+
+trait Schema {
+    type Row: Trace;
+}
+
+enum Ints {}
+
+impl Schema for Ints {
+    type Row = i32;
+}
+
+unsafe impl<'root> Reroot<'root> for Ints {
+    type Rerooted = Ints;
+}
+
+// `S` itself is never traced, so only its rows need to be
+#[derive(GC)]
+#[gc(bound = "<S as Schema>::Row: Trace")]
+struct Table<'root, S: Schema> {
+    #[gc]
+    rows: GcVec<'root, <S as Schema>::Row>,
+}
+
+fn main() {
+    {
+        letroot!(pair, value);
+        let pair = pair.gc(Pair {
+            key: GcStore::new(String::from("one")),
+            values: GcVec::<u32>::new(),
+        });
+        pair.values().push(value.gc(1_u32));
+        nocturne::collect();
+
+        for value in &pair.values().borrow() {
+            println!("{} -> {}", pair.key(), value);
+        }
+    }
+
+    {
+        letroot!(table, row);
+        let table = table.gc(Table::<Ints> { rows: GcVec::new() });
+        table.rows().push(row.gc(3));
+        nocturne::collect();
+
+        println!("{:?}", table.rows());
+    }
+    nocturne::collect();
+}
@@ -0,0 +1,47 @@
+#![feature(arbitrary_self_types)]
+
+use nocturne::{letroot, GcStore, GC};
+
+#[derive(GC)]
+struct Pair<'root>(
+    #[gc] GcStore<'root, i32>,
+    #[gc(name = "second")] GcStore<'root, i32>,
+);
+
+#[derive(GC)]
+enum Shape<'root> {
+    Empty,
+    Point(#[gc] GcStore<'root, i32>, #[gc(project)] i32),
+    Line {
+        #[gc]
+        start: GcStore<'root, i32>,
+        #[gc(name = "end")]
+        end: GcStore<'root, i32>,
+    },
+}
+
+fn main() {
+    {
+        letroot!(pair, empty, point, line);
+        let pair = pair.gc(Pair(GcStore::new(1), GcStore::new(2)));
+        let empty = empty.gc(Shape::Empty);
+        let point = point.gc(Shape::Point(GcStore::new(3), 4));
+        let line = line.gc(Shape::Line {
+            start: GcStore::new(5),
+            end: GcStore::new(6),
+        });
+        nocturne::collect();
+
+        println!("{} {}", pair.field_0(), pair.second());
+
+        for shape in [empty, point, line] {
+            if let Some(x) = shape.point_field_0() {
+                println!("point {} {}", x, shape.point_field_1().unwrap());
+            }
+            if let (Some(start), Some(end)) = (shape.line_start(), shape.end()) {
+                println!("line {} {}", start, end);
+            }
+        }
+    }
+    nocturne::collect();
+}
@@ -0,0 +1,117 @@
+use std::alloc::Allocator;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash;
+use std::ops::Deref;
+
+use nocturne_gc::{Trace, Visitor};
+
+use crate::raw::{Reroot, Store, StoreLayout};
+use crate::{Gc, GcStore};
+
+/// Compares and hashes a GC pointer by the identity of the object rather than its value
+///
+/// Can key maps and sets by object, including ones stored in GC objects, where a
+/// `HashSet<ByAddress<GcStore<T>>>` is accessed as a `HashSet<ByAddress<Gc<T>>>`.
+#[repr(transparent)]
+#[derive(Copy, Clone, Default)]
+pub struct ByAddress<P>(pub P);
+
+impl<P> ByAddress<P> {
+    pub fn into_inner(self) -> P {
+        self.0
+    }
+}
+
+impl<P> Deref for ByAddress<P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        &self.0
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for ByAddress<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ByAddress").field(&self.0).finish()
+    }
+}
+
+macro_rules! by_address {
+    ($($ptr:ty => |$this:ident| $address:expr;)*) => {$(
+        impl<'root, T: ?Sized, A: Allocator> ByAddress<$ptr> {
+            fn address(&self) -> usize {
+                let $this = &self.0;
+                $address as *const () as usize
+            }
+        }
+
+        impl<'root, T: ?Sized, A: Allocator> PartialEq for ByAddress<$ptr> {
+            fn eq(&self, other: &Self) -> bool {
+                self.address() == other.address()
+            }
+        }
+
+        impl<'root, T: ?Sized, A: Allocator> Eq for ByAddress<$ptr> {}
+
+        impl<'root, T: ?Sized, A: Allocator> PartialOrd for ByAddress<$ptr> {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl<'root, T: ?Sized, A: Allocator> Ord for ByAddress<$ptr> {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.address().cmp(&other.address())
+            }
+        }
+
+        impl<'root, T: ?Sized, A: Allocator> hash::Hash for ByAddress<$ptr> {
+            fn hash<H: hash::Hasher>(&self, state: &mut H) {
+                self.address().hash(state)
+            }
+        }
+    )*};
+}
+
+by_address! {
+    Gc<'root, T, A> => |this| Gc::as_ptr(*this);
+    GcStore<'root, T, A> => |this| GcStore::as_ptr(this);
+}
+
+unsafe impl<P: Trace> Trace for ByAddress<P> {
+    unsafe fn mark(&self) {
+        self.0.mark()
+    }
+
+    unsafe fn manage(&self) {
+        self.0.manage()
+    }
+
+    unsafe fn finalize(&mut self) {
+        self.0.finalize()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        self.0.visit(visitor)
+    }
+}
+
+unsafe impl<'root, P: Reroot<'root>> Reroot<'root> for ByAddress<P>
+where
+    P::Rerooted: Sized,
+{
+    type Rerooted = ByAddress<P::Rerooted>;
+}
+
+unsafe impl<'root, P: Store<'root>> Store<'root> for ByAddress<P> {
+    type Accessor = ByAddress<P::Accessor>;
+    unsafe fn rooted(this: &'root Self) -> Self::Accessor {
+        ByAddress(P::rooted(&this.0))
+    }
+}
+
+// `ByAddress` compares the address of the data, which rooting leaves unchanged
+unsafe impl<'root, P: StoreLayout<'root>> StoreLayout<'root> for ByAddress<P> {
+    type Rooted = ByAddress<P::Rooted>;
+}
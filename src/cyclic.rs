@@ -0,0 +1,38 @@
+use std::alloc::{Allocator, Global};
+use std::marker::PhantomData;
+
+use nocturne_gc::GcPtr;
+
+use crate::GcStore;
+
+/// A handle to an object under construction, given to the closure of `Root::gc_cyclic`
+///
+/// The object cannot be read before it is built, so the handle only creates stores pointing to
+/// it, to be put in the object itself or in the objects it points to. Stores that escape the
+/// closure panic when read before the object is built.
+pub struct Cyclic<'root, T: ?Sized + 'root, A: Allocator = Global> {
+    ptr: GcPtr<T, A>,
+    _marker: PhantomData<&'root T>,
+}
+
+impl<'root, T: ?Sized, A: Allocator> Clone for Cyclic<'root, T, A> {
+    fn clone(&self) -> Cyclic<'root, T, A> {
+        *self
+    }
+}
+
+impl<'root, T: ?Sized, A: Allocator> Copy for Cyclic<'root, T, A> {}
+
+impl<'root, T: ?Sized, A: Allocator> Cyclic<'root, T, A> {
+    pub(crate) unsafe fn new(ptr: GcPtr<T, A>) -> Cyclic<'root, T, A> {
+        Cyclic {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a store pointing to the object
+    pub fn store(self) -> GcStore<'root, T, A> {
+        unsafe { GcStore::from_raw(self.ptr) }
+    }
+}
@@ -2,11 +2,13 @@
 use std::alloc::Global;
 use std::fmt;
 use std::hash;
-use std::marker::{PhantomData, PhantomPinned};
-use std::ops::Deref;
+use std::marker::{PhantomData, PhantomPinned, Unsize};
+use std::ops::{CoerceUnsized, Deref};
 use std::pin::Pin;
 
-use nocturne_gc::{GcPtr, Trace};
+use nocturne_gc::{GcPtr, RetainingPath, Trace, Visitor};
+
+use crate::GcRef;
 
 pub struct Gc<'root, T: ?Sized + 'root, A: Allocator = Global> {
     ptr: GcPtr<T, A>,
@@ -21,12 +23,22 @@ fn clone(&self) -> Gc<'root, T, A> {
 
 impl<'root, T: ?Sized, A: Allocator> Copy for Gc<'root, T, A> {}
 
+impl<'root, T, U, A> CoerceUnsized<Gc<'root, U, A>> for Gc<'root, T, A>
+where
+    T: ?Sized + Unsize<U>,
+    U: ?Sized,
+    A: Allocator,
+{
+}
+
 unsafe impl<'root, T: Trace + ?Sized, A: Allocator> Trace for Gc<'root, T, A> {
     unsafe fn mark(&self) {}
 
     unsafe fn manage(&self) {}
 
     unsafe fn finalize(&mut self) {}
+
+    fn visit(&self, _: &mut dyn Visitor) {}
 }
 
 impl<'root, T: ?Sized, A: Allocator> Gc<'root, T, A> {
@@ -45,6 +57,34 @@ pub fn pin(self) -> Pin<Gc<'root, T, A>> {
     pub fn raw(this: Gc<'root, T, A>) -> GcPtr<T, A> {
         this.ptr
     }
+
+    /// Get a raw pointer to the data, valid for as long as `this` is
+    pub fn as_ptr(this: Gc<'root, T, A>) -> *const T {
+        unsafe { this.ptr.data_ptr() }
+    }
+
+    /// Project the pointer to a part of the object, which stays rooted by the same root
+    pub fn map<U: ?Sized, F>(this: Gc<'root, T, A>, f: F) -> GcRef<'root, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let data: &'root T = unsafe { &*(this.ptr.data() as *const T) };
+        unsafe { GcRef::new(f(data)) }
+    }
+
+    /// Tell if both pointers point to the same object, unlike `==` which compares the values
+    pub fn ptr_eq(this: Gc<'root, T, A>, other: Gc<'_, T, A>) -> bool {
+        Gc::as_ptr(this) as *const () == Gc::as_ptr(other) as *const ()
+    }
+}
+
+/// Find the shortest chain of objects keeping `gc` reachable, and the root slot it starts from
+///
+/// Since `gc` is itself rooted, the chain may be just the object if no older root reaches it.
+pub fn retaining_path<T: ?Sized, A: Allocator + 'static>(
+    gc: Gc<'_, T, A>,
+) -> Option<RetainingPath> {
+    nocturne_gc::retaining_path(Gc::raw(gc))
 }
 
 impl<'root, T: ?Sized, A: Allocator> Deref for Gc<'root, T, A> {
@@ -68,6 +108,12 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     }
 }
 
+impl<'root, T: ?Sized, A: Allocator> fmt::Pointer for Gc<'root, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Pointer::fmt(&Gc::as_ptr(*self), f)
+    }
+}
+
 impl<'root, T: PartialEq + ?Sized, A: Allocator> PartialEq for Gc<'root, T, A> {
     fn eq(&self, rhs: &Self) -> bool {
         unsafe { self.ptr.data() == rhs.ptr.data() }
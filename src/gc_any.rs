@@ -0,0 +1,57 @@
+use std::alloc::Allocator;
+use std::any::Any;
+
+use nocturne_gc::Trace;
+
+use crate::raw::Reroot;
+use crate::Gc;
+
+/// A GC object of any type, which can be recovered with `Gc::downcast`
+///
+/// Any `'static` GC type coerces to `Gc<dyn GcAny>`. Types with a `'root` lifetime are erased
+/// with `Gc::into_any`, and recovered by downcasting to their `'static` form.
+pub trait GcAny: Trace + Any {}
+
+impl<T: Trace + Any> GcAny for T {}
+
+unsafe impl<'root> Reroot<'root> for dyn GcAny {
+    type Rerooted = dyn GcAny;
+}
+
+impl<'root, T: ?Sized, A: Allocator> Gc<'root, T, A> {
+    /// Erase the type of the object, keeping the `'root` brand on the pointer
+    pub fn into_any(this: Gc<'root, T, A>) -> Gc<'root, dyn GcAny, A>
+    where
+        T: Reroot<'static>,
+        T::Rerooted: GcAny + Sized,
+    {
+        unsafe {
+            let ptr = Gc::raw(this).cast::<T::Rerooted>();
+            Gc::rooted(ptr)
+        }
+    }
+}
+
+impl<'root, A: Allocator> Gc<'root, dyn GcAny, A> {
+    /// Tell if the object is a `T`, given in its `'static` form
+    pub fn is<T: Any>(self) -> bool {
+        let any: &dyn Any = &*self;
+        any.is::<T>()
+    }
+
+    /// Recover the type of the object, given in its `'static` form
+    ///
+    /// The result is branded with `'root` again, so `downcast::<GcStore<'static, u32>>` gives a
+    /// `Gc<'root, GcStore<'root, u32>>`.
+    pub fn downcast<T>(self) -> Option<Gc<'root, T::Rerooted, A>>
+    where
+        T: Reroot<'root> + Any,
+        T::Rerooted: Sized,
+    {
+        if self.is::<T>() {
+            unsafe { Some(Gc::rooted(Gc::raw(self).cast())) }
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::alloc::{Allocator, Global};
+use std::borrow::Borrow;
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use nocturne_gc::{GcCell, GcCellRef, Trace, Visitor};
+
+use crate::raw::Reroot;
+use crate::{Gc, GcStore, Root};
+
+/// How a `GcHashMap` hashes and compares its keys
+pub trait KeyHashing<K: ?Sized> {
+    fn hash<H: Hasher>(key: &K, state: &mut H);
+    fn eq(a: &K, b: &K) -> bool;
+}
+
+/// Hashes and compares keys by value, so any equal key finds an entry
+pub enum ByValue {}
+
+/// Hashes and compares keys by the identity of the object, so only the key itself finds an entry
+pub enum ByIdentity {}
+
+impl<K: Hash + Eq + ?Sized> KeyHashing<K> for ByValue {
+    fn hash<H: Hasher>(key: &K, state: &mut H) {
+        key.hash(state)
+    }
+
+    fn eq(a: &K, b: &K) -> bool {
+        a == b
+    }
+}
+
+impl<K: ?Sized> KeyHashing<K> for ByIdentity {
+    fn hash<H: Hasher>(key: &K, state: &mut H) {
+        (key as *const K as *const () as usize).hash(state)
+    }
+
+    fn eq(a: &K, b: &K) -> bool {
+        a as *const K as *const () == b as *const K as *const ()
+    }
+}
+
+/// A key as looked up, hashed according to `H`
+#[repr(transparent)]
+struct KeyRef<K: ?Sized, H: ?Sized>(PhantomData<H>, K);
+
+impl<K: ?Sized, H: ?Sized> KeyRef<K, H> {
+    fn new(key: &K) -> &KeyRef<K, H> {
+        unsafe { &*(key as *const K as *const KeyRef<K, H>) }
+    }
+}
+
+impl<K: ?Sized, H: KeyHashing<K> + ?Sized> Hash for KeyRef<K, H> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        H::hash(&self.1, state)
+    }
+}
+
+impl<K: ?Sized, H: KeyHashing<K> + ?Sized> PartialEq for KeyRef<K, H> {
+    fn eq(&self, other: &Self) -> bool {
+        H::eq(&self.1, &other.1)
+    }
+}
+
+impl<K: ?Sized, H: KeyHashing<K> + ?Sized> Eq for KeyRef<K, H> {}
+
+/// A key as stored, which is only hashed while the map keeps it alive
+struct Key<'root, K: ?Sized + 'root, H: ?Sized, A: Allocator> {
+    store: GcStore<'root, K, A>,
+    _marker: PhantomData<H>,
+}
+
+impl<'root, K: ?Sized, H: ?Sized, A: Allocator> Borrow<KeyRef<K, H>> for Key<'root, K, H, A> {
+    fn borrow(&self) -> &KeyRef<K, H> {
+        unsafe { KeyRef::new(&*GcStore::as_ptr(&self.store)) }
+    }
+}
+
+impl<'root, K: ?Sized, H: KeyHashing<K> + ?Sized, A: Allocator> Hash for Key<'root, K, H, A> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        Borrow::<KeyRef<K, H>>::borrow(self).hash(state)
+    }
+}
+
+impl<'root, K: ?Sized, H: KeyHashing<K> + ?Sized, A: Allocator> PartialEq for Key<'root, K, H, A> {
+    fn eq(&self, other: &Self) -> bool {
+        Borrow::<KeyRef<K, H>>::borrow(self) == other.borrow()
+    }
+}
+
+impl<'root, K: ?Sized, H: KeyHashing<K> + ?Sized, A: Allocator> Eq for Key<'root, K, H, A> {}
+
+unsafe impl<'root, K: Trace + ?Sized, H: ?Sized, A: Allocator + 'static> Trace
+    for Key<'root, K, H, A>
+{
+    unsafe fn mark(&self) {
+        self.store.mark()
+    }
+
+    unsafe fn manage(&self) {
+        self.store.manage()
+    }
+
+    unsafe fn finalize(&mut self) {
+        self.store.finalize()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        self.store.visit(visitor)
+    }
+}
+
+/// A hash map from GC objects to GC objects, which can be modified while stored in a GC object
+///
+/// Keys are hashed by value, or by identity with `ByIdentity`. Like with `GcVec`, entries are
+/// read through `borrow`, which prevents replacing or removing them while the `Gc`s read are in
+/// use.
+pub struct GcHashMap<
+    'root,
+    K: ?Sized + 'root,
+    V: ?Sized + 'root,
+    H: ?Sized = ByValue,
+    A: Allocator + 'static = Global,
+> {
+    entries: GcCell<Entries<'root, K, V, H, A>>,
+}
+
+type Entries<'root, K, V, H, A> = HashMap<Key<'root, K, H, A>, GcStore<'root, V, A>>;
+
+impl<'root, K, V, H> GcHashMap<'root, K, V, H>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+{
+    pub fn new() -> GcHashMap<'root, K, V, H> {
+        GcHashMap::with_allocator()
+    }
+}
+
+impl<'root, K, V, H> Default for GcHashMap<'root, K, V, H>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+{
+    fn default() -> GcHashMap<'root, K, V, H> {
+        GcHashMap::new()
+    }
+}
+
+impl<'root, K, V, H, A> GcHashMap<'root, K, V, H, A>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+    A: Allocator + 'static,
+{
+    pub fn with_allocator() -> GcHashMap<'root, K, V, H, A> {
+        GcHashMap {
+            entries: GcCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.borrow().contains_key(KeyRef::new(key))
+    }
+
+    /// Borrow the entries to read them, panicking if they are being modified
+    ///
+    /// Modifying the map panics until the borrow ends.
+    pub fn borrow(&self) -> GcHashMapRef<'_, 'root, K, V, H, A> {
+        GcHashMapRef {
+            entries: self.entries.borrow(),
+        }
+    }
+
+    /// Map `key` to `value`, returning whether the key was new
+    ///
+    /// The key already in the map is kept if there is one. Panics if the map is borrowed.
+    pub fn insert<K2, V2>(&self, key: Gc<'root, K2, A>, value: Gc<'root, V2, A>) -> bool
+    where
+        K2: Reroot<'root, Rerooted = K> + ?Sized,
+        V2: Reroot<'root, Rerooted = V> + ?Sized,
+    {
+        let mut entries = self.entries.borrow_mut();
+        let (key, value) = unsafe {
+            let key = GcStore::from_raw(crate::root::reroot(Gc::raw(key)));
+            let value = GcStore::from_raw(crate::root::reroot(Gc::raw(value)));
+            (key, value)
+        };
+        let key = Key {
+            store: key,
+            _marker: PhantomData,
+        };
+        if let Some(slot) = entries.get_mut(Borrow::<KeyRef<K, H>>::borrow(&key)) {
+            *slot = value;
+            return false;
+        }
+        entries.insert(key, value);
+        true
+    }
+
+    /// Remove the entry of `key`, rooting its value with `root` as nothing else may hold it
+    ///
+    /// Panics if the map is borrowed.
+    pub fn remove<'new_root>(
+        &self,
+        key: &K,
+        root: Root<'new_root, A>,
+    ) -> Option<Gc<'new_root, V::Rerooted, A>>
+    where
+        V: Reroot<'new_root>,
+        V::Rerooted: Trace,
+    {
+        let value = self.entries.borrow_mut().remove(KeyRef::new(key))?;
+        unsafe { Some(root.reroot(Gc::rooted(GcStore::raw(&value)))) }
+    }
+}
+
+impl<'root, K, V, H, A> fmt::Debug for GcHashMap<'root, K, V, H, A>
+where
+    K: Trace + fmt::Debug + ?Sized,
+    V: Trace + fmt::Debug + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+    A: Allocator + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(&self.borrow()).finish()
+    }
+}
+
+/// A borrow of the entries of a `GcHashMap`, which keeps them from being replaced or removed
+pub struct GcHashMapRef<'a, 'root, K, V, H, A>
+where
+    K: ?Sized + 'root,
+    V: ?Sized + 'root,
+    H: ?Sized,
+    A: Allocator + 'static,
+{
+    entries: GcCellRef<'a, Entries<'root, K, V, H, A>>,
+}
+
+impl<'a, 'root, K, V, H, A> GcHashMapRef<'a, 'root, K, V, H, A>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+    A: Allocator + 'static,
+{
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(KeyRef::new(key))
+    }
+
+    pub fn get(&self, key: &K) -> Option<Gc<'_, V, A>> {
+        let value = self.entries.get(KeyRef::new(key))?;
+        unsafe { Some(Gc::rooted(GcStore::raw(value))) }
+    }
+
+    pub fn iter(&self) -> GcHashMapIter<'_, 'root, K, V, H, A> {
+        GcHashMapIter {
+            entries: self.entries.iter(),
+        }
+    }
+}
+
+impl<'b, 'a, 'root, K, V, H, A> IntoIterator for &'b GcHashMapRef<'a, 'root, K, V, H, A>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+    A: Allocator + 'static,
+{
+    type Item = (Gc<'b, K, A>, Gc<'b, V, A>);
+    type IntoIter = GcHashMapIter<'b, 'root, K, V, H, A>;
+
+    fn into_iter(self) -> GcHashMapIter<'b, 'root, K, V, H, A> {
+        self.iter()
+    }
+}
+
+/// Iterates over the entries of a borrowed `GcHashMap`, in no particular order
+pub struct GcHashMapIter<'a, 'root, K, V, H, A>
+where
+    K: ?Sized + 'root,
+    V: ?Sized + 'root,
+    H: ?Sized,
+    A: Allocator + 'static,
+{
+    entries: hash_map::Iter<'a, Key<'root, K, H, A>, GcStore<'root, V, A>>,
+}
+
+impl<'a, 'root, K, V, H, A> Iterator for GcHashMapIter<'a, 'root, K, V, H, A>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: ?Sized,
+    A: Allocator + 'static,
+{
+    type Item = (Gc<'a, K, A>, Gc<'a, V, A>);
+
+    fn next(&mut self) -> Option<(Gc<'a, K, A>, Gc<'a, V, A>)> {
+        let (key, value) = self.entries.next()?;
+        unsafe {
+            Some((
+                Gc::rooted(GcStore::raw(&key.store)),
+                Gc::rooted(GcStore::raw(value)),
+            ))
+        }
+    }
+}
+
+unsafe impl<'root, K, V, H, A> Trace for GcHashMap<'root, K, V, H, A>
+where
+    K: Trace + ?Sized,
+    V: Trace + ?Sized,
+    H: KeyHashing<K> + ?Sized,
+    A: Allocator + 'static,
+{
+    unsafe fn mark(&self) {
+        self.entries.mark()
+    }
+
+    unsafe fn manage(&self) {
+        self.entries.manage()
+    }
+
+    unsafe fn finalize(&mut self) {
+        self.entries.finalize()
+    }
+
+    fn external_size(&self) -> usize {
+        self.entries.external_size()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        self.entries.visit(visitor)
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+use std::ops::Deref;
+
+/// A reference into a GC object, valid for as long as the object is rooted
+///
+/// Obtained by projecting a `Gc` with `Gc::map`.
+pub struct GcRef<'root, T: ?Sized + 'root> {
+    data: &'root T,
+}
+
+impl<'root, T: ?Sized> Clone for GcRef<'root, T> {
+    fn clone(&self) -> GcRef<'root, T> {
+        *self
+    }
+}
+
+impl<'root, T: ?Sized> Copy for GcRef<'root, T> {}
+
+impl<'root, T: ?Sized> GcRef<'root, T> {
+    pub(crate) unsafe fn new(data: &'root T) -> GcRef<'root, T> {
+        GcRef { data }
+    }
+
+    /// Project the reference further
+    pub fn map<U: ?Sized, F>(this: GcRef<'root, T>, f: F) -> GcRef<'root, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        GcRef { data: f(this.data) }
+    }
+
+    /// Get the reference with its full lifetime
+    pub fn get(this: GcRef<'root, T>) -> &'root T {
+        this.data
+    }
+}
+
+impl<'root, T: ?Sized> Deref for GcRef<'root, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<'root, T: fmt::Debug + ?Sized> fmt::Debug for GcRef<'root, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GcRef({:?})", self.data)
+    }
+}
+
+impl<'root, T: fmt::Display + ?Sized> fmt::Display for GcRef<'root, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        T::fmt(self.data, f)
+    }
+}
@@ -1,9 +1,10 @@
 use std::{
     alloc::{Allocator, Global},
-    marker::{PhantomData, PhantomPinned},
+    marker::{PhantomData, PhantomPinned, Unsize},
+    ops::CoerceUnsized,
 };
 
-use nocturne_gc::{GcPtr, Trace};
+use nocturne_gc::{GcPtr, Trace, Visitor};
 
 use crate::Gc;
 
@@ -12,7 +13,16 @@ pub struct GcStore<'root, T: ?Sized + 'root, A: Allocator = Global> {
     _marker: PhantomData<(&'root T, PhantomPinned)>,
 }
 
+impl<'root, T, U, A> CoerceUnsized<GcStore<'root, U, A>> for GcStore<'root, T, A>
+where
+    T: ?Sized + Unsize<U>,
+    U: ?Sized,
+    A: Allocator,
+{
+}
+
 impl<'root, T: Trace> GcStore<'root, T> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn new(data: T) -> GcStore<'root, T> {
         GcStore {
             ptr: nocturne_gc::alloc_unmanaged(data),
@@ -22,6 +32,7 @@ pub fn new(data: T) -> GcStore<'root, T> {
 }
 
 impl<'root, T: Trace, A: Allocator> GcStore<'root, T, A> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn new_in(data: T, allocator: A) -> GcStore<'root, T, A> {
         GcStore {
             ptr: nocturne_gc::alloc_unmanaged_in(data, allocator),
@@ -31,6 +42,13 @@ pub fn new_in(data: T, allocator: A) -> GcStore<'root, T, A> {
 }
 
 impl<'root, T: ?Sized, A: Allocator> GcStore<'root, T, A> {
+    pub(crate) unsafe fn from_raw(ptr: GcPtr<T, A>) -> GcStore<'root, T, A> {
+        GcStore {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
     pub fn get(&self) -> &T {
         unsafe {
             if self.ptr.is_unmanaged() {
@@ -62,6 +80,11 @@ pub fn get_mut_maybe(&mut self) -> Option<&mut T> {
     pub fn raw(this: &GcStore<'root, T, A>) -> GcPtr<T, A> {
         this.ptr
     }
+
+    /// Get a raw pointer to the data, valid for as long as `this` is
+    pub fn as_ptr(this: &GcStore<'root, T, A>) -> *const T {
+        unsafe { this.ptr.data_ptr() }
+    }
 }
 
 unsafe impl<'root, T: Trace + ?Sized, A: Allocator + 'static> Trace for GcStore<'root, T, A> {
@@ -74,6 +97,10 @@ unsafe fn manage(&self) {
     }
 
     unsafe fn finalize(&mut self) {}
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        self.ptr.visit(visitor)
+    }
 }
 
 impl<'root, T: ?Sized + Trace, A: Allocator> From<Gc<'root, T, A>> for GcStore<'root, T, A> {
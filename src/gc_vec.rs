@@ -0,0 +1,166 @@
+use std::alloc::{Allocator, Global};
+use std::fmt;
+use std::slice;
+
+use nocturne_gc::{GcCell, GcCellRef, Trace, Visitor};
+
+use crate::raw::Reroot;
+use crate::{Gc, GcStore, Root};
+
+/// A growable vector of GC objects, which can be modified while stored in a GC object
+///
+/// Elements are only kept alive by the vector, so they are read through `borrow`, which prevents
+/// replacing or removing them while the `Gc`s read are in use.
+pub struct GcVec<'root, T: ?Sized + 'root, A: Allocator + 'static = Global> {
+    items: GcCell<Vec<GcStore<'root, T, A>>>,
+}
+
+impl<'root, T: Trace + ?Sized> GcVec<'root, T> {
+    pub fn new() -> GcVec<'root, T> {
+        GcVec::with_allocator()
+    }
+}
+
+impl<'root, T: Trace + ?Sized> Default for GcVec<'root, T> {
+    fn default() -> GcVec<'root, T> {
+        GcVec::new()
+    }
+}
+
+impl<'root, T: Trace + ?Sized, A: Allocator + 'static> GcVec<'root, T, A> {
+    pub fn with_allocator() -> GcVec<'root, T, A> {
+        GcVec {
+            items: GcCell::new(Vec::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Borrow the elements to read them, panicking if they are being modified
+    ///
+    /// Modifying the vector panics until the borrow ends.
+    pub fn borrow(&self) -> GcVecRef<'_, 'root, T, A> {
+        GcVecRef {
+            items: self.items.borrow(),
+        }
+    }
+
+    /// Replace the element at `idx`, panicking if it is out of bounds or borrowed
+    pub fn set<U>(&self, idx: usize, gc: Gc<'root, U, A>)
+    where
+        U: Reroot<'root, Rerooted = T> + ?Sized,
+    {
+        self.items.borrow_mut()[idx] = Self::store(gc);
+    }
+
+    pub fn push<U>(&self, gc: Gc<'root, U, A>)
+    where
+        U: Reroot<'root, Rerooted = T> + ?Sized,
+    {
+        self.items.borrow_mut().push(Self::store(gc));
+    }
+
+    /// Remove the last element, rooting it with `root` as nothing else may hold it
+    pub fn pop<'new_root>(&self, root: Root<'new_root, A>) -> Option<Gc<'new_root, T::Rerooted, A>>
+    where
+        T: Reroot<'new_root>,
+        T::Rerooted: Trace,
+    {
+        let item = self.items.borrow_mut().pop()?;
+        unsafe { Some(root.reroot(Gc::rooted(GcStore::raw(&item)))) }
+    }
+
+    fn store<U>(gc: Gc<'root, U, A>) -> GcStore<'root, T, A>
+    where
+        U: Reroot<'root, Rerooted = T> + ?Sized,
+    {
+        unsafe { GcStore::from_raw(crate::root::reroot(Gc::raw(gc))) }
+    }
+}
+
+impl<'root, T: Trace + fmt::Debug + ?Sized, A: Allocator + 'static> fmt::Debug
+    for GcVec<'root, T, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(&self.borrow()).finish()
+    }
+}
+
+/// A borrow of the elements of a `GcVec`, which keeps them from being replaced or removed
+pub struct GcVecRef<'a, 'root, T: ?Sized + 'root, A: Allocator + 'static> {
+    items: GcCellRef<'a, Vec<GcStore<'root, T, A>>>,
+}
+
+impl<'a, 'root, T: Trace + ?Sized, A: Allocator + 'static> GcVecRef<'a, 'root, T, A> {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<Gc<'_, T, A>> {
+        let item = self.items.get(idx)?;
+        unsafe { Some(Gc::rooted(GcStore::raw(item))) }
+    }
+
+    pub fn iter(&self) -> GcVecIter<'_, 'root, T, A> {
+        GcVecIter {
+            items: self.items.iter(),
+        }
+    }
+}
+
+impl<'b, 'a, 'root, T: Trace + ?Sized, A: Allocator + 'static> IntoIterator
+    for &'b GcVecRef<'a, 'root, T, A>
+{
+    type Item = Gc<'b, T, A>;
+    type IntoIter = GcVecIter<'b, 'root, T, A>;
+
+    fn into_iter(self) -> GcVecIter<'b, 'root, T, A> {
+        self.iter()
+    }
+}
+
+/// Iterates over the elements of a borrowed `GcVec`
+pub struct GcVecIter<'a, 'root, T: ?Sized + 'root, A: Allocator + 'static> {
+    items: slice::Iter<'a, GcStore<'root, T, A>>,
+}
+
+impl<'a, 'root, T: Trace + ?Sized, A: Allocator + 'static> Iterator for GcVecIter<'a, 'root, T, A> {
+    type Item = Gc<'a, T, A>;
+
+    fn next(&mut self) -> Option<Gc<'a, T, A>> {
+        let item = self.items.next()?;
+        unsafe { Some(Gc::rooted(GcStore::raw(item))) }
+    }
+}
+
+unsafe impl<'root, T: Trace + ?Sized, A: Allocator + 'static> Trace for GcVec<'root, T, A> {
+    unsafe fn mark(&self) {
+        self.items.mark()
+    }
+
+    unsafe fn manage(&self) {
+        self.items.manage()
+    }
+
+    unsafe fn finalize(&mut self) {
+        self.items.finalize()
+    }
+
+    fn external_size(&self) -> usize {
+        self.items.external_size()
+    }
+
+    fn visit(&self, visitor: &mut dyn Visitor) {
+        self.items.visit(visitor)
+    }
+}
@@ -0,0 +1,35 @@
+use std::alloc::Allocator;
+
+use nocturne_gc::Trace;
+
+use crate::root::{reroot, Reroot};
+use crate::{Gc, Root};
+
+/// Root `gc` under `name` until it is unregistered, for the rest of the thread at most
+///
+/// Returns whether a global of the same name was replaced.
+pub fn register_global<T, A>(name: &'static str, gc: Gc<'_, T, A>) -> bool
+where
+    T: Reroot<'static> + ?Sized,
+    T::Rerooted: Trace,
+    A: Allocator + 'static,
+{
+    unsafe { nocturne_gc::register_global(name, reroot(Gc::raw(gc))) }
+}
+
+/// Root the global `name` in `root`, `None` if it is missing or not a `T`
+///
+/// The global may be unregistered afterwards, `root` keeps the object alive regardless.
+pub fn global<'root, T>(name: &str, root: Root<'root>) -> Option<Gc<'root, T::Rerooted>>
+where
+    T: Reroot<'root> + Trace + ?Sized + 'static,
+    T::Rerooted: Trace,
+{
+    let ptr = nocturne_gc::global::<T>(name)?;
+    unsafe { Some(root.make(ptr)) }
+}
+
+/// Stop rooting the global `name`, returning whether it was registered
+pub fn unregister_global(name: &str) -> bool {
+    nocturne_gc::unregister_global(name)
+}
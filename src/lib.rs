@@ -1,7 +1,14 @@
-#![feature(allocator_api)]
+#![feature(allocator_api, coerce_unsized, unsize)]
 
+mod by_address;
+mod cyclic;
 mod gc;
+mod gc_any;
+mod gc_hash_map;
+mod gc_ref;
 mod gc_store;
+mod gc_vec;
+mod global;
 mod no_trace;
 mod root;
 mod store;
@@ -10,20 +17,45 @@
 mod tests;
 
 pub use nocturne_derive::*;
-pub use nocturne_gc::collect;
+pub use nocturne_gc::{add_hook, remove_hook, stats, GcEvent, GcStats, HookId};
+pub use nocturne_gc::{add_memory_pressure, remove_memory_pressure};
+#[cfg(feature = "allocation-profile")]
+pub use nocturne_gc::{allocation_profile, AllocationSite};
+pub use nocturne_gc::{
+    collect, set_collection_threshold, set_max_heap_size, try_collect, FinalizerPanics,
+    GcAllocError,
+};
+pub use nocturne_gc::{GcCell, GcCellRef, GcCellRefMut, GcOnceCell};
 
 pub mod raw {
+    pub use crate::gc::retaining_path;
     pub use crate::root::Reroot;
     pub use crate::store::*;
-    pub use nocturne_gc::{alloc, alloc_unmanaged, manage, GcPtr, Root};
-    pub use nocturne_gc::{count_managed_objects, count_roots};
-    pub use nocturne_gc::{NullTrace, Trace};
+    pub use nocturne_gc::{alloc, alloc_uninit, alloc_unmanaged, manage, write};
+    pub use nocturne_gc::{collect_if_needed, collection_threshold, external_size};
+    pub use nocturne_gc::{count_managed_objects, count_roots, globals, heap_size, max_heap_size};
+    pub use nocturne_gc::{set_finalizer_panic_hook, take_finalizer_panic_hook};
+    pub use nocturne_gc::{try_alloc, try_alloc_in};
+    pub use nocturne_gc::{GcPtr, Root, RootSegment};
+    pub use nocturne_gc::{
+        HeapDiff, HeapSnapshot, LeakedObject, ObjectInfo, RetainingPath, RootId,
+    };
+    pub use nocturne_gc::{NullTrace, Trace, Visitor};
 }
 
+pub use self::by_address::*;
+pub use self::cyclic::*;
 pub use self::gc::*;
+pub use self::gc_any::*;
+pub use self::gc_hash_map::{
+    ByIdentity, ByValue, GcHashMap, GcHashMapIter, GcHashMapRef, KeyHashing,
+};
+pub use self::gc_ref::*;
 pub use self::gc_store::*;
+pub use self::gc_vec::{GcVec, GcVecIter, GcVecRef};
+pub use self::global::*;
 pub use self::no_trace::*;
-pub use self::root::{HeapRoot, Root};
+pub use self::root::{AsyncRoot, HeapRoot, Root, RootCell, RootScope};
 
 pub trait Finalize {
     fn finalize(&mut self);
@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
-use nocturne_gc::Trace;
+use nocturne_gc::{Trace, Visitor};
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct NoTrace<T: ?Sized> {
@@ -43,4 +43,5 @@ unsafe impl<T: ?Sized> Trace for NoTrace<T> {
     unsafe fn mark(&self) {}
     unsafe fn manage(&self) {}
     unsafe fn finalize(&mut self) {}
+    fn visit(&self, _: &mut dyn Visitor) {}
 }
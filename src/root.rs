@@ -1,7 +1,13 @@
+mod async_root;
 mod heap_root;
 mod reroot;
+mod root_cell;
+mod root_scope;
 mod stack_root;
 
+pub use self::async_root::*;
 pub use self::heap_root::*;
 pub use self::reroot::*;
+pub use self::root_cell::*;
+pub use self::root_scope::*;
 pub use self::stack_root::*;
@@ -0,0 +1,108 @@
+use std::alloc::{Allocator, Global};
+
+use nocturne_gc::{GcAllocError, GcPtr, Trace};
+
+use crate::root::Reroot;
+use crate::Gc;
+
+/// A root that can be held across `.await` points
+///
+/// Stack roots are released in the order they were created, which interleaving futures do not
+/// respect. An `AsyncRoot` owns an order-independent slot instead, and the pointers it hands out
+/// borrow it, so rooting another object first requires them to be dead. It is not `Send`, so
+/// futures holding one must run on a local executor.
+pub struct AsyncRoot<A: Allocator + 'static = Global> {
+    root: nocturne_gc::Root<A>,
+}
+
+impl AsyncRoot {
+    pub fn new() -> AsyncRoot {
+        AsyncRoot {
+            root: nocturne_gc::Root::new(),
+        }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn gc<'root, T>(&'root mut self, data: T) -> Gc<'root, T::Rerooted>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        nocturne_gc::collect_if_needed();
+        unsafe { self.make(nocturne_gc::alloc_unmanaged(data)) }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_gc<'root, T>(
+        &'root mut self,
+        data: T,
+    ) -> Result<Gc<'root, T::Rerooted>, GcAllocError>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        unsafe { Ok(self.make(nocturne_gc::try_alloc(data)?)) }
+    }
+}
+
+impl Default for AsyncRoot {
+    fn default() -> AsyncRoot {
+        AsyncRoot::new()
+    }
+}
+
+impl<A: Allocator + 'static> AsyncRoot<A> {
+    pub fn with_allocator() -> AsyncRoot<A> {
+        AsyncRoot {
+            root: nocturne_gc::Root::with_allocator(),
+        }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn gc_in<'root, T>(&'root mut self, data: T, allocator: A) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        nocturne_gc::collect_if_needed_with_allocator::<A>();
+        unsafe { self.make(nocturne_gc::alloc_unmanaged_in(data, allocator)) }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_gc_in<'root, T>(
+        &'root mut self,
+        data: T,
+        allocator: A,
+    ) -> Result<Gc<'root, T::Rerooted, A>, GcAllocError>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        unsafe { Ok(self.make(nocturne_gc::try_alloc_in(data, allocator)?)) }
+    }
+
+    pub fn reroot<'root, T>(&'root mut self, gc: Gc<'_, T, A>) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + ?Sized,
+        T::Rerooted: Trace,
+    {
+        unsafe { self.make(Gc::raw(gc)) }
+    }
+
+    unsafe fn make<'root, T>(&'root mut self, ptr: GcPtr<T, A>) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + ?Sized,
+        T::Rerooted: Trace,
+    {
+        let ptr = super::reroot(ptr);
+        self.root.enroot(ptr);
+        Gc::rooted(ptr)
+    }
+}
+
+#[macro_export]
+macro_rules! root_async {
+    ($($root:ident),*) => {$(
+        let mut $root = $crate::AsyncRoot::new();
+    )*};
+}
@@ -7,10 +7,8 @@
 use crate::root::Reroot;
 use crate::Gc;
 
-/// TODO Completely unsound, roots currently always behave as stack roots (pop_root etc.)
-/// In other words, the heap root lifetime may be bigger than overlapping stack roots created before
-/// Scenario: Stack root, Box and return a heap root, stack root popped, then heap root popped (wrong order)
-/// Just need to support arbitrary root drops
+/// TODO Not finished, see `make`. Root slots can now be released in any order, so a heap root
+/// outliving stack roots created before it is fine.
 pub struct HeapRoot<T: ?Sized, A: Allocator + 'static = Global> {
     _root: Pin<Box<Root<A>, A>>,
     ptr: GcPtr<T, A>,
@@ -21,6 +19,7 @@ impl<'root, T> HeapRoot<T>
     T: Reroot<'root> + Trace,
     T::Rerooted: Trace,
 {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn new(data: T) -> HeapRoot<T::Rerooted> {
         unsafe { HeapRoot::make(nocturne_gc::alloc(data)) }
     }
@@ -31,6 +30,7 @@ impl<'root, T, A: Allocator + Clone + 'static> HeapRoot<T, A>
     T: Reroot<'root> + Trace,
     T::Rerooted: Trace,
 {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn new_in(data: T, allocator: A) -> HeapRoot<T::Rerooted, A> {
         unsafe { HeapRoot::make(nocturne_gc::alloc_in(data, allocator)) }
     }
@@ -4,7 +4,7 @@
 
 use nocturne_gc::{GcPtr, NullTrace, Trace};
 
-use crate::{Gc, GcStore};
+use crate::{Gc, GcHashMap, GcStore, GcVec};
 
 pub unsafe trait Reroot<'root> {
     type Rerooted: ?Sized + 'root;
@@ -33,15 +33,44 @@ unsafe impl<'root, 'r2, T: Reroot<'root> + ?Sized> Reroot<'root> for GcStore<'r2
     type Rerooted = GcStore<'root, T::Rerooted>;
 }
 
+unsafe impl<'root, 'r2, T, A> Reroot<'root> for GcVec<'r2, T, A>
+where
+    T: Reroot<'root> + ?Sized,
+    A: Allocator + 'static,
+{
+    type Rerooted = GcVec<'root, T::Rerooted, A>;
+}
+
+unsafe impl<'root, 'r2, K, V, H, A> Reroot<'root> for GcHashMap<'r2, K, V, H, A>
+where
+    K: Reroot<'root> + ?Sized,
+    V: Reroot<'root> + ?Sized,
+    H: ?Sized + 'root,
+    A: Allocator + 'static,
+{
+    type Rerooted = GcHashMap<'root, K::Rerooted, V::Rerooted, H, A>;
+}
+
 unsafe impl<'root, T: Reroot<'root> + ?Sized> Reroot<'root> for pin_cell::PinCell<T> {
     type Rerooted = pin_cell::PinCell<T::Rerooted>;
 }
 
+unsafe impl<'root, T: Reroot<'root> + ?Sized> Reroot<'root> for nocturne_gc::GcCell<T> {
+    type Rerooted = nocturne_gc::GcCell<T::Rerooted>;
+}
+
+unsafe impl<'root, T: Reroot<'root>> Reroot<'root> for nocturne_gc::GcOnceCell<T>
+where
+    T::Rerooted: Sized,
+{
+    type Rerooted = nocturne_gc::GcOnceCell<T::Rerooted>;
+}
+
 unsafe impl<'root, T: NullTrace + Reroot<'root> + ?Sized> Reroot<'root> for cell::Cell<T> {
     type Rerooted = cell::Cell<T::Rerooted>;
 }
 
-unsafe impl<'root, T: NullTrace + Reroot<'root> + ?Sized> Reroot<'root> for cell::RefCell<T> {
+unsafe impl<'root, T: Reroot<'root> + ?Sized> Reroot<'root> for cell::RefCell<T> {
     type Rerooted = cell::RefCell<T::Rerooted>;
 }
 
@@ -88,6 +117,7 @@ macro_rules! reroot_simple {
     std::sync::Barrier
     std::sync::Condvar
     std::sync::Once
+    std::collections::hash_map::RandomState
 );
 
 unsafe impl<'root, T: Reroot<'root>, const N: usize> Reroot<'root> for [T; N]
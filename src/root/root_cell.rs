@@ -0,0 +1,43 @@
+use std::alloc::{Allocator, Global};
+use std::pin::Pin;
+
+use nocturne_gc::{GcPtr, Trace};
+
+use crate::root::Reroot;
+use crate::Gc;
+
+/// A stack root whose object can be replaced
+///
+/// Created with `letroot!(cell = gc)`. The pointers handed out by `get` borrow the cell, so they
+/// cannot outlive the object being overwritten by `set`.
+pub struct RootCell<'root, T: ?Sized + 'root, A: Allocator + 'static = Global> {
+    root: Pin<&'root mut nocturne_gc::Root<A>>,
+    ptr: GcPtr<T, A>,
+}
+
+impl<'root, T: ?Sized + 'root, A: Allocator + 'static> RootCell<'root, T, A> {
+    pub(crate) unsafe fn new(
+        root: Pin<&'root mut nocturne_gc::Root<A>>,
+        ptr: GcPtr<T, A>,
+    ) -> RootCell<'root, T, A> {
+        RootCell { root, ptr }
+    }
+
+    /// Get the rooted object
+    pub fn get(&self) -> Gc<'_, T, A> {
+        unsafe { Gc::rooted(self.ptr) }
+    }
+
+    /// Root `gc` in place of the current object
+    pub fn set<U>(&mut self, gc: Gc<'_, U, A>)
+    where
+        U: Reroot<'root, Rerooted = T> + ?Sized,
+        T: Trace,
+    {
+        unsafe {
+            let ptr = super::reroot(Gc::raw(gc));
+            self.root.enroot(ptr);
+            self.ptr = ptr;
+        }
+    }
+}
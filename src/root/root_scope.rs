@@ -0,0 +1,97 @@
+use std::alloc::{Allocator, Global};
+
+use nocturne_gc::{GcAllocError, GcPtr, RootSegment, Trace};
+
+use crate::root::Reroot;
+use crate::Gc;
+
+/// A stack root that can hold any number of objects, all living until the scope ends
+///
+/// Unlike `Root`, rooting an object only borrows the scope, so a single `letroots!` can stand in
+/// for any number of `letroot!` slots.
+pub struct RootScope<'root, A: Allocator + 'static = Global> {
+    segment: &'root RootSegment<A>,
+}
+
+impl<'root> RootScope<'root> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn gc<T>(&self, data: T) -> Gc<'root, T::Rerooted>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        nocturne_gc::collect_if_needed();
+        unsafe { self.make(nocturne_gc::alloc_unmanaged(data)) }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_gc<T>(&self, data: T) -> Result<Gc<'root, T::Rerooted>, GcAllocError>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        unsafe { Ok(self.make(nocturne_gc::try_alloc(data)?)) }
+    }
+}
+
+impl<'root, A: Allocator + 'static> RootScope<'root, A> {
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn gc_in<T>(&self, data: T, allocator: A) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        nocturne_gc::collect_if_needed_with_allocator::<A>();
+        unsafe { self.make(nocturne_gc::alloc_unmanaged_in(data, allocator)) }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_gc_in<T>(
+        &self,
+        data: T,
+        allocator: A,
+    ) -> Result<Gc<'root, T::Rerooted, A>, GcAllocError>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        unsafe { Ok(self.make(nocturne_gc::try_alloc_in(data, allocator)?)) }
+    }
+
+    #[doc(hidden)]
+    pub unsafe fn new(segment: &'root mut RootSegment<A>) -> RootScope<'root, A> {
+        RootScope { segment }
+    }
+
+    pub fn reroot<T>(&self, gc: Gc<'_, T, A>) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + ?Sized,
+        T::Rerooted: Trace,
+    {
+        unsafe { self.make(Gc::raw(gc)) }
+    }
+
+    unsafe fn make<T>(&self, ptr: GcPtr<T, A>) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + ?Sized,
+        T::Rerooted: Trace,
+    {
+        let ptr = super::reroot(ptr);
+        self.segment.enroot(ptr);
+        Gc::rooted(ptr)
+    }
+}
+
+#[macro_export]
+macro_rules! letroots {
+    ($($scope:ident),*) => {$(
+        // Ensure the segment is owned
+        let mut $scope = $crate::raw::RootSegment::new();
+
+        // Shadow the original binding so that it can't be directly accessed
+        // ever again.
+        let $scope = unsafe {
+            $crate::RootScope::new(&mut $scope)
+        };
+    )*};
+}
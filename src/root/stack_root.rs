@@ -1,9 +1,10 @@
 use std::alloc::{Allocator, Global};
 use std::pin::Pin;
 
-use nocturne_gc::{GcPtr, Trace};
+use nocturne_gc::{GcAllocError, GcPtr, Trace};
 
-use crate::root::Reroot;
+use crate::root::{Reroot, RootCell};
+use crate::Cyclic;
 use crate::Gc;
 
 pub struct Root<'root, A: Allocator + 'static = Global> {
@@ -11,24 +12,82 @@ pub struct Root<'root, A: Allocator + 'static = Global> {
 }
 
 impl<'root> Root<'root> {
+    /// Allocate an object, even past the maximum heap size, which only `try_gc` enforces
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn gc<T>(self, data: T) -> Gc<'root, T::Rerooted>
     where
         T: Reroot<'root> + Trace,
         T::Rerooted: Trace,
     {
+        nocturne_gc::collect_if_needed();
         unsafe { self.make(nocturne_gc::alloc_unmanaged(data)) }
     }
+
+    /// Allocate an object that can point to itself
+    ///
+    /// `f` builds the object from a handle to it, which cannot be read until `f` returns: reading
+    /// it through a store made from the handle panics until then, or forever if `f` panics.
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn gc_cyclic<T, F>(self, f: F) -> Gc<'root, T::Rerooted>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace + Sized,
+        F: FnOnce(Cyclic<'root, T::Rerooted>) -> T,
+    {
+        nocturne_gc::collect_if_needed();
+        unsafe { self.make_cyclic(nocturne_gc::alloc_uninit(), f) }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_gc<T>(self, data: T) -> Result<Gc<'root, T::Rerooted>, GcAllocError>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        unsafe { Ok(self.make(nocturne_gc::try_alloc(data)?)) }
+    }
 }
 
 impl<'root, A: Allocator + 'static> Root<'root, A> {
+    /// Allocate an object, even past the maximum heap size, which only `try_gc_in` enforces
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
     pub fn gc_in<T>(self, data: T, allocator: A) -> Gc<'root, T::Rerooted, A>
     where
         T: Reroot<'root> + Trace,
         T::Rerooted: Trace,
     {
+        nocturne_gc::collect_if_needed_with_allocator::<A>();
         unsafe { self.make(nocturne_gc::alloc_unmanaged_in(data, allocator)) }
     }
 
+    /// Allocate an object that can point to itself
+    ///
+    /// `f` builds the object from a handle to it, which cannot be read until `f` returns: reading
+    /// it through a store made from the handle panics until then, or forever if `f` panics.
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn gc_cyclic_in<T, F>(self, f: F, allocator: A) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace + Sized,
+        F: FnOnce(Cyclic<'root, T::Rerooted, A>) -> T,
+    {
+        nocturne_gc::collect_if_needed_with_allocator::<A>();
+        unsafe { self.make_cyclic(nocturne_gc::alloc_uninit_in(allocator), f) }
+    }
+
+    #[cfg_attr(feature = "allocation-profile", track_caller)]
+    pub fn try_gc_in<T>(
+        self,
+        data: T,
+        allocator: A,
+    ) -> Result<Gc<'root, T::Rerooted, A>, GcAllocError>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace,
+    {
+        unsafe { Ok(self.make(nocturne_gc::try_alloc_in(data, allocator)?)) }
+    }
+
     #[doc(hidden)]
     pub unsafe fn new(root: &'root mut nocturne_gc::Root<A>) -> Root<'root, A> {
         Root {
@@ -44,6 +103,19 @@ pub fn reroot<T>(self, gc: Gc<'_, T, A>) -> Gc<'root, T::Rerooted, A>
         unsafe { self.make(Gc::raw(gc)) }
     }
 
+    /// Turn the root into a cell holding `gc`, which can later be replaced
+    pub fn cell<T>(mut self, gc: Gc<'_, T, A>) -> RootCell<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + ?Sized,
+        T::Rerooted: Trace,
+    {
+        unsafe {
+            let ptr = super::reroot(Gc::raw(gc));
+            self.emplace(ptr);
+            RootCell::new(self.root, ptr)
+        }
+    }
+
     pub(crate) unsafe fn make<T>(mut self, ptr: GcPtr<T, A>) -> Gc<'root, T::Rerooted, A>
     where
         T: Reroot<'root> + ?Sized,
@@ -54,6 +126,19 @@ pub(crate) unsafe fn make<T>(mut self, ptr: GcPtr<T, A>) -> Gc<'root, T::Reroote
         Gc::rooted(ptr)
     }
 
+    unsafe fn make_cyclic<T, F>(mut self, ptr: GcPtr<T, A>, f: F) -> Gc<'root, T::Rerooted, A>
+    where
+        T: Reroot<'root> + Trace,
+        T::Rerooted: Trace + Sized,
+        F: FnOnce(Cyclic<'root, T::Rerooted, A>) -> T,
+    {
+        // Root the object first, as `f` may trigger a collection
+        self.emplace(ptr);
+        let data = f(Cyclic::new(ptr.cast()));
+        nocturne_gc::write(ptr, data);
+        self.make(ptr)
+    }
+
     unsafe fn emplace<T: Trace + ?Sized>(&mut self, ptr: GcPtr<T, A>) {
         Pin::get_mut(self.root.as_mut()).enroot(ptr)
     }
@@ -83,4 +168,10 @@ macro_rules! letroot {
             $crate::Root::new(&mut $root)
         };
     )*};
+    ($($root:ident = $init:expr),*) => {$(
+        $crate::letroot!($root);
+
+        #[allow(unused_mut)]
+        let mut $root = $root.cell($init);
+    )*};
 }
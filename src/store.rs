@@ -1,10 +1,36 @@
-use crate::{Gc, GcStore};
+use crate::{Gc, GcHashMap, GcStore, GcVec};
 
+/// A stored type which can be read through a rooted `Gc`, as the accessor of a `#[gc]` field
+///
+/// Containers which can be written through a shared reference without a borrow, like `Cell`,
+/// only store data without GC pointers:
+///
+/// ```compile_fail
+/// use nocturne::{raw::Store, GcStore};
+/// use std::cell::Cell;
+///
+/// fn read<'root>(cell: &'root Cell<Option<GcStore<'root, u32>>>) {
+///     let _ = unsafe { Store::rooted(cell) };
+/// }
+/// ```
 pub unsafe trait Store<'root> {
     type Accessor: 'root;
     unsafe fn rooted(this: &'root Self) -> Self::Accessor;
 }
 
+/// A stored type which can be read in place as `Rooted`, its `GcStore`s read as `Gc`s
+///
+/// Implementing it for a container gives it a `Store` accessing it as `&'root Rooted`, so any
+/// nesting of such containers can be accessed.
+///
+/// # Safety
+///
+/// `Rooted` must have the layout of `Self`, with each `GcStore` replaced by a `Gc` to the same
+/// object, or by a view reading it as such.
+pub unsafe trait StoreLayout<'root> {
+    type Rooted: 'root;
+}
+
 unsafe impl<'root, 'r, T: ?Sized + 'root, A: Allocator + 'static> Store<'root>
     for GcStore<'r, T, A>
 {
@@ -14,28 +40,238 @@ unsafe fn rooted(this: &'root Self) -> Self::Accessor {
     }
 }
 
-macro_rules! transmute_store {
-    ($(for<$($T:ident),*> $from:ty => $to:ty;)*) => {$(
-        unsafe impl<'root, 'r, A: Allocator + 'static, $($T: ?Sized + 'root,)*> Store<'root> for $from {
+unsafe impl<'root, 'r, T: ?Sized + 'root, A: Allocator + 'static> StoreLayout<'root>
+    for GcStore<'r, T, A>
+{
+    type Rooted = Gc<'root, T, A>;
+}
+
+macro_rules! layout_store {
+    ($([$($T:ident),*; $($generics:tt)*] $from:ty => $to:ty;)*) => {$(
+        unsafe impl<'root, $($T: StoreLayout<'root>,)* $($generics)*> StoreLayout<'root> for $from
+        where
+            $from: 'root,
+        {
+            type Rooted = $to;
+        }
+
+        unsafe impl<'root, $($T: StoreLayout<'root>,)* $($generics)*> Store<'root> for $from
+        where
+            $from: 'root,
+        {
             type Accessor = &'root $to;
             unsafe fn rooted(this: &'root $from) -> &'root $to {
-                std::mem::transmute::<&'root $from, &'root $to>(this)
+                &*(this as *const $from as *const $to)
             }
         }
     )*}
 }
 
+use nocturne_gc::{GcCell, GcCellRef, GcOnceCell, NullTrace, Trace};
 use pin_cell::PinCell;
+use std::cell::{BorrowError, Cell, Ref, RefCell};
+use std::rc::Rc;
 use std::{alloc::Allocator, collections::*};
 
-transmute_store! {
-    for<T> Box<GcStore<'r, T, A>, A> => Box<Gc<'root, T, A>, A>;
-    for<T> Option<GcStore<'r, T, A>> => Option<Gc<'root, T, A>>;
-    for<T> [GcStore<'r, T, A>] => [Gc<'root, T, A>];
-    for<T> Vec<GcStore<'r, T, A>, A> => Vec<Gc<'root, T, A>, A>;
-    for<T> VecDeque<GcStore<'r, T, A>, A> => VecDeque<Gc<'root, T, A>, A>;
-    for<T> HashSet<GcStore<'r, T, A>, A> => HashSet<Gc<'root, T, A>, A>;
-    for<T> BTreeSet<GcStore<'r, T, A>> => BTreeSet<Gc<'root, T, A>>;
-    for<T> BinaryHeap<GcStore<'r, T, A>> => BinaryHeap<Gc<'root, T, A>>;
-    for<T> PinCell<GcStore<'r, T, A>> => PinCell<Gc<'root, T, A>>;
+layout_store! {
+    [T; B: Allocator] Box<T, B> => Box<T::Rooted, B>;
+    [T;] Option<T> => Option<T::Rooted>;
+    [T, E;] Result<T, E> => Result<T::Rooted, E::Rooted>;
+    [T; const N: usize] [T; N] => [T::Rooted; N];
+    [T; B: Allocator] Vec<T, B> => Vec<T::Rooted, B>;
+    [T; B: Allocator] VecDeque<T, B> => VecDeque<T::Rooted, B>;
+    [T; B: Allocator] LinkedList<T, B> => LinkedList<T::Rooted, B>;
+    [T; S] HashSet<T, S> => HashSet<T::Rooted, S>;
+    [K, V; S] HashMap<K, V, S> => HashMap<K::Rooted, V::Rooted, S>;
+    [T;] BTreeSet<T> => BTreeSet<T::Rooted>;
+    [K, V;] BTreeMap<K, V> => BTreeMap<K::Rooted, V::Rooted>;
+    [T;] BinaryHeap<T> => BinaryHeap<T::Rooted>;
+    [T;] Rc<T> => Rc<T::Rooted>;
+    [T;] PinCell<T> => PinCell<T::Rooted>;
+    [T;] RefCell<T> => RefCellView<T>;
+    [T;] GcCell<T> => GcCellView<T>;
+    [T;] GcOnceCell<T> => GcOnceCell<T::Rooted>;
+}
+
+// A `Cell` can be replaced without a borrow, so it is read as is
+unsafe impl<'root, T: NullTrace + 'root> StoreLayout<'root> for Cell<T> {
+    type Rooted = Cell<T>;
+}
+
+unsafe impl<'root, T: NullTrace + 'root> Store<'root> for Cell<T> {
+    type Accessor = &'root Cell<T>;
+    unsafe fn rooted(this: &'root Cell<T>) -> &'root Cell<T> {
+        this
+    }
+}
+
+/// A stored `RefCell`, whose contents are read as `Rooted` only while they are borrowed
+///
+/// The contents cannot be replaced during the borrow, so the `Gc`s read from them stay valid.
+#[repr(transparent)]
+pub struct RefCellView<T: ?Sized> {
+    cell: RefCell<T>,
+}
+
+impl<T: ?Sized> RefCellView<T> {
+    /// Immutably borrow the contents, panicking if they are mutably borrowed
+    pub fn borrow(&self) -> RefCellViewRef<'_, T> {
+        self.try_borrow()
+            .expect("RefCell is already mutably borrowed")
+    }
+
+    pub fn try_borrow(&self) -> Result<RefCellViewRef<'_, T>, BorrowError> {
+        Ok(RefCellViewRef {
+            value: self.cell.try_borrow()?,
+        })
+    }
+}
+
+/// An immutable borrow of the contents of a stored `RefCell`
+pub struct RefCellViewRef<'a, T: ?Sized> {
+    value: Ref<'a, T>,
+}
+
+impl<'a, T> RefCellViewRef<'a, T> {
+    /// Read the contents, for no longer than the borrow
+    pub fn get<'b>(&'b self) -> &'b T::Rooted
+    where
+        T: StoreLayout<'b>,
+    {
+        unsafe { &*(&*self.value as *const T as *const T::Rooted) }
+    }
+}
+
+/// A stored `GcCell`, whose contents are read as `Rooted` only while they are borrowed
+///
+/// The contents cannot be replaced during the borrow, so the `Gc`s read from them stay valid.
+#[repr(transparent)]
+pub struct GcCellView<T: ?Sized> {
+    cell: GcCell<T>,
+}
+
+impl<T: Trace + ?Sized> GcCellView<T> {
+    /// Immutably borrow the contents, panicking if they are mutably borrowed
+    pub fn borrow(&self) -> GcCellViewRef<'_, T> {
+        self.try_borrow()
+            .expect("GcCell is already mutably borrowed")
+    }
+
+    pub fn try_borrow(&self) -> Result<GcCellViewRef<'_, T>, BorrowError> {
+        Ok(GcCellViewRef {
+            value: self.cell.try_borrow()?,
+        })
+    }
+}
+
+/// An immutable borrow of the contents of a stored `GcCell`
+pub struct GcCellViewRef<'a, T: ?Sized> {
+    value: GcCellRef<'a, T>,
+}
+
+impl<'a, T> GcCellViewRef<'a, T> {
+    /// Read the contents, for no longer than the borrow
+    pub fn get<'b>(&'b self) -> &'b T::Rooted
+    where
+        T: StoreLayout<'b>,
+    {
+        unsafe { &*(&*self.value as *const T as *const T::Rooted) }
+    }
+}
+
+// Slices are unsized, so they only implement `Store`
+unsafe impl<'root, T: StoreLayout<'root>> Store<'root> for [T]
+where
+    T: 'root,
+{
+    type Accessor = &'root [T::Rooted];
+    unsafe fn rooted(this: &'root [T]) -> &'root [T::Rooted] {
+        &*(this as *const [T] as *const [T::Rooted])
+    }
+}
+
+macro_rules! layout_store_tuples {
+    ($(($($T:ident),*))*) => {$(
+        layout_store! {
+            [$($T),*;] ($($T,)*) => ($($T::Rooted,)*);
+        }
+    )*};
+}
+
+layout_store_tuples! {
+    (A)
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+    (A, B, C, D, E, F, G)
+    (A, B, C, D, E, F, G, H)
+    (A, B, C, D, E, F, G, H, I)
+    (A, B, C, D, E, F, G, H, I, J)
+    (A, B, C, D, E, F, G, H, I, J, K)
+    (A, B, C, D, E, F, G, H, I, J, K, L)
+}
+
+// Data without GC pointers is read as is
+macro_rules! layout_simple {
+    ($($t:ty)*) => {$(unsafe impl<'root> StoreLayout<'root> for $t {
+        type Rooted = $t;
+    })*}
+}
+
+layout_simple!(
+    ()
+    i8  i16 i32 i64 isize
+    u8  u16 u32 u64 usize
+    f32     f64
+    char    bool
+    String
+    std::path::PathBuf
+);
+
+unsafe impl<'root, 'r, T, A> StoreLayout<'root> for GcVec<'r, T, A>
+where
+    T: ?Sized + 'root,
+    A: Allocator + 'static,
+{
+    type Rooted = GcVec<'root, T, A>;
+}
+
+unsafe impl<'root, 'r, T, A> Store<'root> for GcVec<'r, T, A>
+where
+    T: ?Sized + 'root,
+    A: Allocator + 'static,
+{
+    type Accessor = &'root GcVec<'root, T, A>;
+    // Only the lifetime changes
+    #[allow(clippy::unnecessary_cast)]
+    unsafe fn rooted(this: &'root Self) -> Self::Accessor {
+        &*(this as *const Self as *const GcVec<'root, T, A>)
+    }
+}
+
+unsafe impl<'root, 'r, K, V, H, A> StoreLayout<'root> for GcHashMap<'r, K, V, H, A>
+where
+    K: ?Sized + 'root,
+    V: ?Sized + 'root,
+    H: ?Sized + 'root,
+    A: Allocator + 'static,
+{
+    type Rooted = GcHashMap<'root, K, V, H, A>;
+}
+
+unsafe impl<'root, 'r, K, V, H, A> Store<'root> for GcHashMap<'r, K, V, H, A>
+where
+    K: ?Sized + 'root,
+    V: ?Sized + 'root,
+    H: ?Sized + 'root,
+    A: Allocator + 'static,
+{
+    type Accessor = &'root GcHashMap<'root, K, V, H, A>;
+    // Only the lifetime changes
+    #[allow(clippy::unnecessary_cast)]
+    unsafe fn rooted(this: &'root Self) -> Self::Accessor {
+        &*(this as *const Self as *const GcHashMap<'root, K, V, H, A>)
+    }
 }
@@ -50,3 +50,1293 @@ fn rerooting() {
     collect();
     assert_eq!(raw::count_managed_objects(), 0);
 }
+
+struct PanicOnFinalize(&'static str);
+
+unsafe impl raw::Trace for PanicOnFinalize {
+    unsafe fn mark(&self) {}
+    unsafe fn manage(&self) {}
+    unsafe fn finalize(&mut self) {
+        panic!("{}", self.0)
+    }
+    fn visit(&self, _: &mut dyn raw::Visitor) {}
+}
+
+unsafe impl<'root> raw::Reroot<'root> for PanicOnFinalize {
+    type Rerooted = PanicOnFinalize;
+}
+
+struct PanicOnMark;
+
+unsafe impl raw::Trace for PanicOnMark {
+    unsafe fn mark(&self) {
+        panic!("mark")
+    }
+    unsafe fn manage(&self) {}
+    unsafe fn finalize(&mut self) {}
+    fn visit(&self, _: &mut dyn raw::Visitor) {}
+}
+
+unsafe impl<'root> raw::Reroot<'root> for PanicOnMark {
+    type Rerooted = PanicOnMark;
+}
+
+#[test]
+fn finalizer_panics() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(first, second, third);
+        first.gc(PanicOnFinalize("first"));
+        second.gc(0xDEADBEEF_u32);
+        third.gc(PanicOnFinalize("third"));
+    }
+
+    // Every object is freed even though two finalizers panic
+    let panics = try_collect().unwrap_err().into_panics();
+    assert_eq!(raw::count_managed_objects(), 0);
+    let mut messages: Vec<&str> = panics
+        .iter()
+        .map(|p| p.downcast_ref::<String>().unwrap().as_str())
+        .collect();
+    messages.sort_unstable();
+    assert_eq!(messages, ["first", "third"]);
+
+    // The heap is still usable afterwards
+    letroot!(root);
+    let ptr = root.gc(0xBADCAFE);
+    collect();
+    assert_eq!(*ptr, 0xBADCAFE);
+}
+
+#[test]
+fn finalizer_panic_hook() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let _ = env_logger::try_init();
+
+    let reported = Rc::new(Cell::new(0));
+    let counter = reported.clone();
+    raw::set_finalizer_panic_hook(move |_| counter.set(counter.get() + 1));
+
+    {
+        letroot!(root);
+        root.gc(PanicOnFinalize("hooked"));
+    }
+    collect();
+
+    assert!(raw::take_finalizer_panic_hook().is_some());
+    assert_eq!(reported.get(), 1);
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn heap_limit() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let ptr = root.try_gc(0xBADCAFE_u64).unwrap();
+        let object_size = raw::heap_size();
+        assert!(object_size > 0);
+
+        // Room for exactly two objects, one of which is already used
+        set_max_heap_size(Some(object_size * 2));
+
+        letroot!(second, third);
+        assert_eq!(*second.try_gc(1_u64).unwrap(), 1);
+        assert!(matches!(
+            third.try_gc(2_u64),
+            Err(GcAllocError::HeapLimitExceeded { .. })
+        ));
+        assert_eq!(*ptr, 0xBADCAFE);
+    }
+
+    // Unrooted garbage is collected to make room before failing
+    letroot!(first, second);
+    first.try_gc(1_u64).unwrap();
+    second.try_gc(2_u64).unwrap();
+    assert_eq!(raw::count_managed_objects(), 2);
+
+    set_max_heap_size(None);
+}
+
+#[test]
+fn heap_limit_nested() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        root.try_gc(Some(GcStore::new(1_u64))).unwrap();
+    }
+    let size = raw::heap_size();
+    collect();
+
+    // The outer object fits, but not with the one it holds
+    set_max_heap_size(Some(size - 1));
+    letroot!(root);
+    assert!(matches!(
+        root.try_gc(Some(GcStore::new(2_u64))),
+        Err(GcAllocError::HeapLimitExceeded { .. })
+    ));
+    assert_eq!(raw::count_managed_objects(), 0);
+
+    set_max_heap_size(None);
+}
+
+#[test]
+fn heap_limit_finalizer_panics() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(garbage);
+        garbage.try_gc(PanicOnFinalize("garbage")).unwrap();
+    }
+    set_max_heap_size(Some(raw::heap_size()));
+
+    // Making room runs the panicking finalizer, which must not unwind out of `try_gc`
+    letroot!(root);
+    assert_eq!(*root.try_gc(0xBADCAFE_u64).unwrap(), 0xBADCAFE);
+    assert_eq!(raw::count_managed_objects(), 1);
+
+    set_max_heap_size(None);
+}
+
+#[test]
+fn allocator_failure() {
+    use std::alloc::{AllocError, Allocator, Layout};
+    use std::ptr::NonNull;
+
+    struct Exhausted;
+
+    unsafe impl Allocator for Exhausted {
+        fn allocate(&self, _: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            Err(AllocError)
+        }
+
+        unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
+            unreachable!()
+        }
+    }
+
+    assert_eq!(
+        raw::try_alloc_in(0_u8, Exhausted).err(),
+        Some(GcAllocError::AllocFailed)
+    );
+}
+
+#[test]
+fn external_memory() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let buffer = root.gc(vec![0_u8; 4096]);
+        assert!(raw::external_size() >= 4096);
+        collect();
+        assert!(raw::external_size() >= 4096);
+        assert_eq!(buffer.len(), 4096);
+    }
+
+    collect();
+    assert_eq!(raw::external_size(), 0);
+
+    {
+        letroot!(root);
+        root.gc(0xBADCAFE);
+    }
+
+    // Allocations only collect once a threshold is set
+    add_memory_pressure(usize::MAX / 2);
+    letroot!(root);
+    let kept = root.gc(0xBEEFDAD);
+    assert_eq!(raw::count_managed_objects(), 2);
+
+    // Enough memory pressure then makes the next allocation collect the garbage first
+    set_collection_threshold(Some(4096));
+    assert_eq!(raw::collection_threshold(), Some(4096));
+    letroot!(other);
+    other.gc(0xFEEDFACE_u32);
+    assert_eq!(raw::count_managed_objects(), 2);
+    assert_eq!(*kept, 0xBEEFDAD);
+    remove_memory_pressure(usize::MAX / 2);
+    assert_eq!(raw::external_size(), 0);
+    set_collection_threshold(None);
+}
+
+#[test]
+fn external_size_growth() {
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let buffer = root.gc(GcCell::new(Vec::<u8>::new()));
+    assert_eq!(raw::external_size(), 0);
+
+    // Growing an object after its allocation counts toward the next automatic collection
+    buffer.borrow_mut().resize(1 << 16, 0);
+    assert!(raw::external_size() >= 1 << 16);
+    {
+        letroot!(inner);
+        inner.gc(0xBADCAFE);
+    }
+    set_collection_threshold(Some(1 << 16));
+    letroot!(other);
+    other.gc(0xBEEFDAD);
+    assert_eq!(raw::count_managed_objects(), 2);
+    set_collection_threshold(None);
+}
+
+#[test]
+fn panic_during_mark() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        root.gc(PanicOnMark);
+        assert!(std::panic::catch_unwind(collect).is_err());
+    }
+
+    // The interrupted collection does not block the next one
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn ref_cell_borrowed_during_collection() {
+    use std::cell::RefCell;
+
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let cell = root.gc(RefCell::new(vec![GcStore::new(1_u32)]));
+
+        // Without a write barrier, the cell must be readable when it is marked
+        let guard = cell.borrow_mut();
+        assert!(std::panic::catch_unwind(collect).is_err());
+        drop(guard);
+
+        cell.borrow_mut().push(GcStore::new(2));
+        collect();
+        assert_eq!(raw::count_managed_objects(), 3);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn collection_hooks() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let _ = env_logger::try_init();
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let hooks: Vec<HookId> = [
+        GcEvent::BeforeMark,
+        GcEvent::AfterMark,
+        GcEvent::BeforeSweep,
+        GcEvent::AfterCollect,
+    ]
+    .into_iter()
+    .map(|event| {
+        let events = events.clone();
+        add_hook(event, move |stats| {
+            // Hooks may call back into the collector, but cannot start a nested collection
+            assert_eq!(raw::count_roots(), 1);
+            if event != GcEvent::AfterCollect {
+                collect();
+            }
+            events.borrow_mut().push((event, *stats));
+        })
+    })
+    .collect();
+
+    letroot!(root);
+    root.gc(0xBADCAFE);
+    {
+        letroot!(garbage);
+        garbage.gc(0xBEEFDAD);
+    }
+    collect();
+
+    let events = events.borrow();
+    let kinds: Vec<GcEvent> = events.iter().map(|(event, _)| *event).collect();
+    assert_eq!(
+        kinds,
+        [
+            GcEvent::BeforeMark,
+            GcEvent::AfterMark,
+            GcEvent::BeforeSweep,
+            GcEvent::AfterCollect
+        ]
+    );
+    let (_, after_mark) = events[1];
+    assert_eq!(after_mark.objects_marked, 1);
+    assert_eq!(after_mark.managed_objects, 2);
+    let (_, after_collect) = events[3];
+    assert_eq!(after_collect.objects_freed, 1);
+    assert_eq!(after_collect.managed_objects, 1);
+    assert!(after_collect.bytes_freed > 0);
+
+    for id in hooks {
+        assert!(remove_hook(id));
+    }
+}
+
+#[test]
+fn hooks_allocate() {
+    let _ = env_logger::try_init();
+
+    // Objects allocated between the mark and sweep phases survive the sweep
+    let hooks = [
+        add_hook(GcEvent::AfterMark, |_| {
+            letroot!(root);
+            register_global("after_mark", root.gc(GcStore::new(0xBADCAFE_u32)));
+        }),
+        add_hook(GcEvent::BeforeSweep, |_| {
+            letroot!(root);
+            register_global("before_sweep", root.gc(0xBEEFDAD_u32));
+        }),
+    ];
+    collect();
+    assert_eq!(raw::count_managed_objects(), 3);
+    for id in hooks {
+        assert!(remove_hook(id));
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 3);
+    {
+        letroot!(root);
+        let store = global::<GcStore<u32>>("after_mark", root).unwrap();
+        assert_eq!(unsafe { *Gc::rooted(GcStore::raw(&*store)) }, 0xBADCAFE);
+        letroot!(root);
+        assert_eq!(*global::<u32>("before_sweep", root).unwrap(), 0xBEEFDAD);
+    }
+
+    assert!(unregister_global("after_mark"));
+    assert!(unregister_global("before_sweep"));
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_spans() {
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    type Fields = HashMap<&'static str, u64>;
+
+    /// Records the numeric fields of every span, by span name
+    #[derive(Clone, Default)]
+    struct Spans {
+        by_id: Arc<Mutex<HashMap<Id, usize>>>,
+        spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+    }
+
+    struct Recorder<'a>(&'a mut Fields);
+
+    impl Visit for Recorder<'_> {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name(), value);
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+    }
+
+    impl<S: Subscriber> Layer<S> for Spans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+            let mut fields = Fields::new();
+            attrs.record(&mut Recorder(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            self.by_id.lock().unwrap().insert(id.clone(), spans.len());
+            spans.push((attrs.metadata().name(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+            let idx = self.by_id.lock().unwrap()[id];
+            values.record(&mut Recorder(&mut self.spans.lock().unwrap()[idx].1));
+        }
+    }
+
+    let _ = env_logger::try_init();
+
+    let spans = Spans::default();
+    let subscriber = tracing_subscriber::registry().with(spans.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        letroot!(root);
+        root.gc(0xBADCAFE_u64);
+        {
+            letroot!(garbage);
+            garbage.gc(0xBEEFDAD_u64);
+        }
+        collect();
+    });
+
+    let spans = spans.spans.lock().unwrap();
+    let names: Vec<&str> = spans.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["collect", "mark", "sweep"]);
+
+    let (_, collect) = &spans[0];
+    assert_eq!(collect["collection"], 1);
+    assert_eq!(collect["objects_marked"], 1);
+    assert_eq!(collect["objects_freed"], 1);
+    assert!(collect["bytes_freed"] > 0);
+    assert!(collect.contains_key("pause_us"));
+
+    let (_, mark) = &spans[1];
+    assert_eq!(mark["objects_marked"], 1);
+
+    let (_, sweep) = &spans[2];
+    assert_eq!(sweep["objects_freed"], 1);
+    assert_eq!(sweep["bytes_freed"], collect["bytes_freed"]);
+}
+
+#[cfg(feature = "allocation-profile")]
+#[test]
+fn allocation_sites() {
+    let _ = env_logger::try_init();
+
+    letroot!(first, second);
+    let (_, first_line) = (first.gc(0xBADCAFE_u64), line!());
+    let (store, store_line) = (GcStore::new(0xBEEFDAD_u64), line!());
+    let (_, second_line) = (second.gc(store), line!());
+
+    let profile = allocation_profile();
+    let mut sites: Vec<(u32, usize)> = profile
+        .iter()
+        .map(|site| (site.location.line(), site.objects))
+        .collect();
+    sites.sort_unstable();
+    assert_eq!(sites, [(first_line, 1), (store_line, 1), (second_line, 1)]);
+    assert!(profile.iter().all(|site| site.location.file() == file!()));
+    let bytes: usize = profile.iter().map(|site| site.bytes).sum();
+    assert_eq!(bytes, raw::heap_size());
+}
+
+#[test]
+fn heap_snapshot_diff() {
+    use std::any::type_name;
+
+    let _ = env_logger::try_init();
+
+    letroot!(global);
+    let slot = raw::count_roots() - 1;
+    let before = raw::HeapSnapshot::capture();
+
+    // A handler that leaves one structure behind and creates some garbage
+    global.gc(GcStore::new(0xBADCAFE_u32));
+    {
+        letroot!(temporary);
+        temporary.gc(0xBEEFDAD_u64);
+    }
+    collect();
+
+    let after = raw::HeapSnapshot::capture();
+    let diff = raw::HeapSnapshot::diff(&before, &after);
+    assert_eq!(diff.len(), 2, "{}", diff);
+    assert!(diff.objects_of(type_name::<u64>()).is_empty());
+
+    let outer = &diff.objects_of(type_name::<GcStore<u32>>())[0];
+    let inner = &diff.objects_of(type_name::<u32>())[0];
+    let path = inner.path.as_ref().unwrap();
+    assert_eq!(path.root, raw::RootId::Stack(slot));
+    assert_eq!(path.objects, [outer.object, inner.object]);
+
+    assert!(raw::HeapSnapshot::diff(&after, &after).is_empty());
+}
+
+#[test]
+fn heap_snapshot_while_borrowed() {
+    use pin_cell::PinCell;
+    use std::pin::Pin;
+
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let cell = root.gc(PinCell::new(Some(GcStore::new(0xBADCAFE_u32))));
+    // GC objects never move
+    let _borrow = PinCell::borrow_mut(unsafe { Pin::new_unchecked(&*cell) });
+
+    // The contents of the borrowed cell are skipped instead of panicking
+    let snapshot = raw::HeapSnapshot::capture();
+    assert_eq!(snapshot.objects().len(), 2);
+}
+
+#[test]
+fn heap_snapshot_while_gc_cell_borrowed() {
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let cell = root.gc(GcCell::new(Some(GcStore::new(0xBADCAFE_u32))));
+    let _borrow = cell.borrow_mut();
+
+    // The contents of the borrowed cell are skipped instead of panicking
+    let snapshot = raw::HeapSnapshot::capture();
+    assert_eq!(snapshot.objects().len(), 2);
+    assert!((0..2).all(|idx| snapshot.references(idx).is_empty()));
+}
+
+#[test]
+fn retaining_path() {
+    use std::any::type_name;
+
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let slot = raw::count_roots() - 1;
+    let outer = root.gc(GcStore::new(0xBADCAFE_u32));
+
+    // The inner value is only reachable through the outer store
+    let inner = unsafe { Gc::rooted(GcStore::raw(&*outer)) };
+    let path = raw::retaining_path(inner).unwrap();
+    assert_eq!(path.root, raw::RootId::Stack(slot));
+    let types: Vec<_> = path.objects.iter().map(|o| o.type_name).collect();
+    assert_eq!(types, [type_name::<GcStore<u32>>(), type_name::<u32>()]);
+
+    // A directly rooted object is retained by its own slot
+    let path = raw::retaining_path(outer).unwrap();
+    assert_eq!(path.root, raw::RootId::Stack(slot));
+    assert_eq!(path.objects.len(), 1);
+}
+
+/// Traced the way `#[derive(GC)]` does, naming its field
+struct Parent<'root> {
+    child: GcStore<'root, u32>,
+}
+
+unsafe impl<'root> raw::Trace for Parent<'root> {
+    unsafe fn mark(&self) {
+        self.child.mark()
+    }
+    unsafe fn manage(&self) {
+        self.child.manage()
+    }
+    unsafe fn finalize(&mut self) {
+        self.child.finalize()
+    }
+    fn visit(&self, visitor: &mut dyn raw::Visitor) {
+        visitor.enter_field("child");
+        self.child.visit(visitor);
+        visitor.leave_field();
+    }
+}
+
+unsafe impl<'root, 'new_root> raw::Reroot<'new_root> for Parent<'root> {
+    type Rerooted = Parent<'new_root>;
+}
+
+#[test]
+fn devtools_snapshot() {
+    use std::any::type_name;
+
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let slot = raw::count_roots() - 1;
+    root.gc(Parent {
+        child: GcStore::new(0xBADCAFE),
+    });
+
+    let mut out = Vec::new();
+    raw::HeapSnapshot::capture()
+        .write_devtools(&mut out)
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    let strings = json["strings"].as_array().unwrap();
+    let string =
+        |idx: &serde_json::Value| strings[idx.as_u64().unwrap() as usize].as_str().unwrap();
+    let nodes = json["nodes"].as_array().unwrap();
+    let edges = json["edges"].as_array().unwrap();
+    let node_count = json["snapshot"]["node_count"].as_u64().unwrap() as usize;
+    assert_eq!(nodes.len(), node_count * 6);
+    assert_eq!(string(&nodes[1]), "(GC roots)");
+
+    // Edges are stored in node order, so walk them alongside the nodes
+    let mut named = Vec::new();
+    let mut edge = 0;
+    for node in nodes.chunks(6) {
+        for _ in 0..node[4].as_u64().unwrap() {
+            let target = &nodes[edges[edge + 2].as_u64().unwrap() as usize..][..6];
+            let label = match edges[edge].as_u64().unwrap() {
+                1 => edges[edge + 1].to_string(),
+                _ => string(&edges[edge + 1]).to_owned(),
+            };
+            named.push((
+                string(&node[1]).to_owned(),
+                label,
+                string(&target[1]).to_owned(),
+            ));
+            edge += 3;
+        }
+    }
+    assert_eq!(edge, edges.len());
+    assert!(named.contains(&(
+        "(GC roots)".to_owned(),
+        slot.to_string(),
+        type_name::<Parent>().to_owned()
+    )));
+    assert!(named.contains(&(
+        type_name::<Parent>().to_owned(),
+        "child".to_owned(),
+        type_name::<u32>().to_owned()
+    )));
+}
+
+#[test]
+fn root_scope() {
+    let _ = env_logger::try_init();
+
+    let roots = raw::count_roots();
+    {
+        letroots!(scope);
+        let values: Vec<Gc<u32>> = (0..100_u32).map(|i| scope.gc(i)).collect();
+
+        // Regular roots can be interleaved with the scope's
+        let last = {
+            letroot!(temporary);
+            let inner = temporary.gc(0xBADCAFE_u32);
+            scope.reroot(inner)
+        };
+
+        collect();
+        assert_eq!(raw::count_roots(), roots + 1);
+        assert_eq!(raw::count_managed_objects(), 101);
+        assert!(values.iter().enumerate().all(|(i, v)| **v == i as u32));
+        assert_eq!(*last, 0xBADCAFE);
+    }
+
+    // Every object of the scope is released at once
+    collect();
+    assert_eq!(raw::count_roots(), roots);
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn root_cell() {
+    let _ = env_logger::try_init();
+
+    letroot!(first);
+    let first = first.gc(0_u32);
+    letroot!(current = first);
+
+    // Replace the working object on every iteration without adding roots
+    let roots = raw::count_roots();
+    for i in 1..10_u32 {
+        letroot!(next);
+        let next = next.gc(*current.get() + i);
+        current.set(next);
+    }
+    assert_eq!(raw::count_roots(), roots);
+
+    collect();
+    assert_eq!(*current.get(), 45);
+    assert_eq!(*first, 0);
+    assert_eq!(raw::count_managed_objects(), 2);
+}
+
+#[test]
+fn global_roots() {
+    use std::any::type_name;
+
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let modules = root.gc(GcStore::new(0xBADCAFE_u32));
+        assert!(!register_global("modules", modules));
+    }
+    collect();
+    assert_eq!(raw::count_managed_objects(), 2);
+
+    let globals = raw::globals();
+    assert_eq!(globals.len(), 1);
+    assert_eq!(globals[0].0, "modules");
+    assert_eq!(globals[0].1.type_name, type_name::<GcStore<u32>>());
+
+    {
+        letroot!(wrong);
+        assert!(global::<u64>("modules", wrong).is_none());
+        letroot!(missing);
+        assert!(global::<GcStore<u32>>("symbols", missing).is_none());
+
+        letroot!(root);
+        let modules = global::<GcStore<u32>>("modules", root).unwrap();
+        let inner = unsafe { Gc::rooted(GcStore::raw(&*modules)) };
+        assert_eq!(*inner, 0xBADCAFE);
+    }
+
+    // Only the registry keeps the objects alive now
+    let snapshot = raw::HeapSnapshot::capture();
+    for path in snapshot.retaining_paths() {
+        assert_eq!(path.unwrap().root, raw::RootId::Global("modules"));
+    }
+
+    assert!(unregister_global("modules"));
+    assert!(!unregister_global("modules"));
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn async_roots() {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Returns `Pending` once, letting the other tasks run
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    async fn work(value: u32, yields: usize) -> u32 {
+        root_async!(root);
+        let gc = root.gc(value);
+        for _ in 0..yields {
+            Yield(false).await;
+            collect();
+        }
+        *gc
+    }
+
+    let _ = env_logger::try_init();
+
+    // Poll the tasks in turn until they all complete, so that their roots are dropped out of order
+    let roots = raw::count_roots();
+    let mut tasks: Vec<Pin<Box<dyn Future<Output = u32>>>> = vec![
+        Box::pin(work(1, 1)),
+        Box::pin(work(2, 3)),
+        Box::pin(work(3, 2)),
+    ];
+    let mut results = vec![None; tasks.len()];
+    let mut context = Context::from_waker(Waker::noop());
+    while results.iter().any(Option::is_none) {
+        for (task, result) in tasks.iter_mut().zip(&mut results) {
+            if result.is_none() {
+                if let Poll::Ready(value) = task.as_mut().poll(&mut context) {
+                    *result = Some(value);
+                }
+            }
+        }
+        // Slots released below live ones are not counted
+        let pending = results.iter().filter(|result| result.is_none()).count();
+        assert_eq!(raw::count_roots(), roots + pending);
+    }
+    assert_eq!(results, [Some(1), Some(2), Some(3)]);
+
+    collect();
+    assert_eq!(raw::count_roots(), roots);
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn identity() {
+    use std::collections::HashSet;
+
+    let _ = env_logger::try_init();
+
+    letroot!(first, second);
+    let first = first.gc(0xBADCAFE_u32);
+    let second = second.gc(0xBADCAFE_u32);
+    assert_eq!(first, second);
+    assert!(!Gc::ptr_eq(first, second));
+    assert!(Gc::ptr_eq(first, first));
+    assert_eq!(format!("{:p}", first), format!("{:p}", Gc::as_ptr(first)));
+
+    let mut set = HashSet::new();
+    set.insert(ByAddress(first));
+    set.insert(ByAddress(second));
+    set.insert(ByAddress(first));
+    assert_eq!(set.len(), 2);
+
+    // The same identity is kept when the pointers are stored in a GC object
+    letroot!(root);
+    let stored: HashSet<_> = [first, second]
+        .into_iter()
+        .map(|gc| ByAddress(GcStore::from(gc)))
+        .collect();
+    let stored = root.gc(stored);
+    collect();
+    let accessed = unsafe { raw::Store::rooted(&*stored) };
+    assert!(accessed.contains(&ByAddress(first)));
+    assert!(accessed.contains(&ByAddress(second)));
+}
+
+#[test]
+fn projection() {
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let pair = root.gc((0xBADCAFE_u32, String::from("nocturne")));
+    let name = Gc::map(pair, |pair| &pair.1);
+    let first = GcRef::map(name, |name| &name[..1]);
+
+    collect();
+    assert_eq!(*name, "nocturne");
+    assert_eq!(format!("{} {:?}", first, first), "n GcRef(\"n\")");
+    assert_eq!(GcRef::get(name) as *const String, &pair.1 as *const String);
+}
+
+#[test]
+fn downcasting() {
+    let _ = env_logger::try_init();
+
+    letroot!(number, text, store, objects);
+    let number: Gc<dyn GcAny> = number.gc(0xBADCAFE_u32);
+    let text: Gc<dyn GcAny> = text.gc(String::from("nocturne"));
+    let store = Gc::into_any(store.gc(GcStore::new(0xBEEFDAD_u64)));
+
+    // Heterogeneous objects are traced through the trait object
+    let objects = objects.gc(vec![
+        GcStore::from(number),
+        GcStore::from(text),
+        GcStore::from(store),
+    ]);
+    collect();
+    assert_eq!(raw::count_managed_objects(), 5);
+
+    assert!(number.is::<u32>());
+    assert!(!number.is::<u64>());
+    assert_eq!(*number.downcast::<u32>().unwrap(), 0xBADCAFE);
+    assert!(number.downcast::<String>().is_none());
+    assert_eq!(*text.downcast::<String>().unwrap(), "nocturne");
+
+    let store = store.downcast::<GcStore<'static, u64>>().unwrap();
+    assert_eq!(*unsafe { raw::Store::rooted(&*store) }, 0xBEEFDAD);
+    assert_eq!(objects.len(), 3);
+}
+
+struct Node<'root> {
+    value: u32,
+    next: Option<GcStore<'root, Node<'root>>>,
+}
+
+unsafe impl<'root> raw::Trace for Node<'root> {
+    unsafe fn mark(&self) {
+        self.next.mark()
+    }
+    unsafe fn manage(&self) {
+        self.next.manage()
+    }
+    unsafe fn finalize(&mut self) {
+        self.next.finalize()
+    }
+    fn visit(&self, visitor: &mut dyn raw::Visitor) {
+        self.next.visit(visitor)
+    }
+}
+
+unsafe impl<'root, 'new_root> raw::Reroot<'new_root> for Node<'root> {
+    type Rerooted = Node<'new_root>;
+}
+
+#[test]
+fn cyclic() {
+    let _ = env_logger::try_init();
+
+    fn next<'a>(node: &'a Node) -> Gc<'a, Node<'a>> {
+        unsafe { raw::Store::rooted(node.next.as_ref().unwrap()) }
+    }
+
+    {
+        letroot!(root);
+        let node = root.gc_cyclic(|this| Node {
+            value: 1,
+            next: Some(this.store()),
+        });
+        collect();
+        assert!(Gc::ptr_eq(next(&node), node));
+
+        // Two objects pointing to each other, built in one step
+        letroot!(root);
+        let first = root.gc_cyclic(|this| Node {
+            value: 2,
+            next: Some(GcStore::new(Node {
+                value: 3,
+                next: Some(this.store()),
+            })),
+        });
+        collect();
+        assert_eq!(raw::count_managed_objects(), 3);
+        let second = next(&first);
+        assert_eq!((first.value, second.value), (2, 3));
+        assert!(Gc::ptr_eq(next(&second), first));
+
+        // Rerooting walks the cycle without looping
+        letroot!(root);
+        assert_eq!(root.reroot(second).value, 3);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn cyclic_escape() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(holder, root);
+        let holder = holder.gc(GcCell::<Option<GcStore<Node>>>::new(None));
+        let read = || unsafe {
+            let cell = raw::Store::rooted(&*holder);
+            let node = cell.borrow();
+            node.get().unwrap().value
+        };
+
+        // A store escaping the closure cannot be read until the object is built
+        let node = root.gc_cyclic(|this| {
+            *holder.borrow_mut() = Some(this.store());
+            assert!(catch_unwind(AssertUnwindSafe(read)).is_err());
+            Node {
+                value: 1,
+                next: None,
+            }
+        });
+        assert_eq!(read(), 1);
+        assert_eq!(node.value, 1);
+
+        // Nor ever, if the closure panics
+        letroot!(root);
+        let built = catch_unwind(AssertUnwindSafe(|| {
+            root.gc_cyclic(|this| -> Node {
+                *holder.borrow_mut() = Some(this.store());
+                panic!("not built")
+            });
+        }));
+        assert!(built.is_err());
+        collect();
+        assert!(catch_unwind(AssertUnwindSafe(read)).is_err());
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_cell() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let cell = root.gc(GcCell::new(Vec::<GcStore<u32>>::new()));
+
+        // Objects stored in a managed cell are managed when the borrow ends
+        cell.borrow_mut().push(GcStore::new(1));
+        assert_eq!(raw::count_managed_objects(), 2);
+        collect();
+        assert_eq!(raw::count_managed_objects(), 2);
+        let items = unsafe { raw::Store::rooted(&*cell) };
+        assert_eq!(*items.borrow().get()[0], 1);
+
+        {
+            letroot!(garbage);
+            garbage.gc(2_u32);
+        }
+
+        // Nothing is traced, and so nothing is collected, while the cell is mutably borrowed
+        let mut guard = cell.borrow_mut();
+        guard.push(GcStore::new(3));
+        assert!(cell.try_borrow().is_err());
+        collect();
+        assert_eq!(raw::count_managed_objects(), 3);
+        drop(guard);
+
+        collect();
+        assert_eq!(raw::count_managed_objects(), 3);
+        let values: Vec<u32> = items.borrow().get().iter().map(|item| **item).collect();
+        assert_eq!(values, [1, 3]);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_cell_replace_while_borrowed() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let cell = root.gc(GcCell::new(Some(GcStore::new(1_u32))));
+        let store = unsafe { raw::Store::rooted(&*cell) };
+
+        // The object read cannot be replaced, and so freed, while it is in use
+        let contents = store.borrow();
+        let item = contents.get().unwrap();
+        let replaced = catch_unwind(AssertUnwindSafe(|| {
+            *cell.borrow_mut() = Some(GcStore::new(2));
+        }));
+        assert!(replaced.is_err());
+        collect();
+        assert_eq!(*item, 1);
+        drop(contents);
+
+        // Once the borrow ends, the object is replaced and freed
+        *cell.borrow_mut() = Some(GcStore::new(2));
+        collect();
+        assert_eq!(raw::count_managed_objects(), 2);
+        assert_eq!(*store.borrow().get().unwrap(), 2);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_once_cell() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let cell = root.gc(GcOnceCell::<GcStore<u32>>::new());
+        assert!(unsafe { raw::Store::rooted(&*cell) }.get().is_none());
+
+        // Collections may happen during initialization, which is then managed with the cell
+        cell.get_or_init(|| {
+            collect();
+            GcStore::new(1)
+        });
+        assert_eq!(raw::count_managed_objects(), 2);
+        assert!(cell.set(GcStore::new(2)).is_err());
+        cell.get_or_init(|| unreachable!());
+
+        collect();
+        assert_eq!(raw::count_managed_objects(), 2);
+        let value = unsafe { raw::Store::rooted(&*cell) }
+            .get()
+            .copied()
+            .unwrap();
+        assert_eq!(*value, 1);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_vec() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(first, second, root);
+        let (first, second) = (first.gc(1_u32), second.gc(2_u32));
+        let vec = root.gc(GcVec::<u32>::new());
+        vec.push(first);
+        vec.push(second);
+        vec.push(first);
+        assert_eq!(vec.len(), 3);
+        assert!(Gc::ptr_eq(vec.borrow().get(0).unwrap(), first));
+        assert!(vec.borrow().get(3).is_none());
+
+        // Elements pushed through a rerooted vector are kept alive by it
+        {
+            letroot!(third, inner);
+            let vec = inner.reroot(vec);
+            vec.set(1, third.gc(3_u32));
+        }
+        collect();
+        assert_eq!(raw::count_managed_objects(), 4);
+        let values: Vec<u32> = vec.borrow().iter().map(|item| *item).collect();
+        assert_eq!(values, [1, 3, 1]);
+
+        letroot!(popped);
+        let first = vec.pop(popped).unwrap();
+        assert_eq!(*first, 1);
+        assert_eq!(format!("{:?}", *vec), "[Gc(1), Gc(3)]");
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_vec_replace_while_borrowed() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let vec = root.gc(GcVec::<u32>::new());
+        {
+            letroot!(item, inner);
+            inner.reroot(vec).push(item.gc(1_u32));
+        }
+
+        // The element read cannot be replaced, and so freed, while it is in use
+        let items = vec.borrow();
+        let item = items.get(0).unwrap();
+        let replaced = catch_unwind(AssertUnwindSafe(|| {
+            letroot!(other, inner);
+            inner.reroot(vec).set(0, other.gc(2_u32));
+        }));
+        assert!(replaced.is_err());
+        collect();
+        assert_eq!(*item, 1);
+        drop(items);
+
+        // Once the borrow ends, the element is replaced and freed
+        {
+            letroot!(other, inner);
+            inner.reroot(vec).set(0, other.gc(2_u32));
+        }
+        collect();
+        assert_eq!(raw::count_managed_objects(), 2);
+        assert_eq!(*vec.borrow().get(0).unwrap(), 2);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_hash_map() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(key, one, root);
+        let key = key.gc(String::from("key"));
+        let one = one.gc(1_u32);
+        let map = root.gc(GcHashMap::<String, u32>::new());
+        assert!(map.insert(key, one));
+
+        // Entries inserted through a rerooted map are kept alive by it
+        {
+            letroot!(other_key, two, inner);
+            let map = inner.reroot(map);
+            assert!(!map.insert(other_key.gc(String::from("key")), two.gc(2_u32)));
+        }
+        collect();
+        assert_eq!(raw::count_managed_objects(), 4);
+        assert_eq!(map.len(), 1);
+        assert_eq!(*map.borrow().get(&String::from("key")).unwrap(), 2);
+        assert!(Gc::ptr_eq(map.borrow().iter().next().unwrap().0, key));
+        assert_eq!(format!("{:?}", *map), r#"{Gc("key"): Gc(2)}"#);
+
+        letroot!(removed);
+        let two = map.remove(&key, removed).unwrap();
+        assert_eq!(*two, 2);
+        assert!(map.is_empty());
+
+        // Identity-hashed keys are only found by the object itself
+        letroot!(root);
+        let map = root.gc(GcHashMap::<String, u32, ByIdentity>::new());
+        map.insert(key, one);
+        assert!(map.contains_key(&key));
+        assert!(!map.contains_key(&String::from("key")));
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn gc_hash_map_replace_while_borrowed() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(key, root);
+        let key = key.gc(1_u32);
+        let map = root.gc(GcHashMap::<u32, u32>::new());
+        {
+            letroot!(value, inner);
+            inner.reroot(map).insert(key, value.gc(2_u32));
+        }
+
+        // The value read cannot be replaced, and so freed, while it is in use
+        let entries = map.borrow();
+        let value = entries.get(&1).unwrap();
+        let replaced = catch_unwind(AssertUnwindSafe(|| {
+            letroot!(other, inner);
+            inner.reroot(map).insert(key, other.gc(3_u32));
+        }));
+        assert!(replaced.is_err());
+        collect();
+        assert_eq!(*value, 2);
+        drop(entries);
+
+        // Once the borrow ends, the value is replaced and freed
+        {
+            letroot!(other, inner);
+            inner.reroot(map).insert(key, other.gc(3_u32));
+        }
+        collect();
+        assert_eq!(raw::count_managed_objects(), 3);
+        assert_eq!(*map.borrow().get(&1).unwrap(), 3);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn nested_stores() {
+    let _ = env_logger::try_init();
+
+    {
+        letroot!(root);
+        let outer = root.gc(GcStore::new(GcStore::new(0xBADCAFE)));
+        assert_eq!(raw::count_managed_objects(), 3);
+        collect();
+        assert_eq!(raw::count_managed_objects(), 3);
+        let inner = unsafe { raw::Store::rooted(&*outer) };
+        let inner: Gc<GcStore<i32>> = inner;
+        assert_eq!(*unsafe { raw::Store::rooted(&*inner) }, 0xBADCAFE);
+    }
+
+    collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn nested_containers() {
+    use std::collections::{BTreeMap, HashMap, LinkedList};
+
+    let _ = env_logger::try_init();
+
+    letroot!(root);
+    let nested = root.gc((
+        Some(vec![GcStore::new(1_u32), GcStore::new(2)]),
+        [GcStore::new(3_u32)],
+        BTreeMap::from([(String::from("four"), GcStore::new(4_u32))]),
+        HashMap::from([(5_u32, LinkedList::from([GcStore::new(6_u32)]))]),
+    ));
+    collect();
+    assert_eq!(raw::count_managed_objects(), 6);
+
+    // Every `GcStore` is read as a `Gc`, however deeply it is nested
+    let (option, array, btree_map, hash_map) = unsafe { raw::Store::rooted(&*nested) };
+    let option: &Option<Vec<Gc<u32>>> = option;
+    let values: Vec<u32> = option.iter().flatten().map(|gc| **gc).collect();
+    assert_eq!(values, [1, 2]);
+    assert_eq!(*array[0], 3);
+    assert_eq!(*btree_map["four"], 4);
+    assert_eq!(**hash_map[&5].front().unwrap(), 6);
+}
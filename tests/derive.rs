@@ -0,0 +1,255 @@
+#![feature(arbitrary_self_types)]
+
+use std::any::type_name;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use nocturne::raw::{self, HeapSnapshot, Reroot, Trace};
+use nocturne::{letroot, Gc, GcStore, GcVec, GC};
+
+#[derive(GC)]
+struct Parent<'root> {
+    #[gc]
+    first: GcStore<'root, u32>,
+    second: GcStore<'root, u64>,
+}
+
+#[derive(GC)]
+struct Pair<'root>(GcStore<'root, u32>, GcStore<'root, u64>);
+
+#[derive(GC)]
+struct Tagged<'root>(
+    #[gc] GcStore<'root, u32>,
+    #[gc(name = "second")] GcStore<'root, u32>,
+);
+
+#[derive(GC)]
+enum Shape<'root> {
+    Empty,
+    Point(#[gc] GcStore<'root, u32>, #[gc(project)] u32),
+    Line {
+        #[gc]
+        start: GcStore<'root, u32>,
+        #[gc(name = "end")]
+        end: GcStore<'root, u32>,
+    },
+}
+
+#[derive(GC)]
+enum Service<'root> {
+    HTTPServer(#[gc] GcStore<'root, u32>),
+    Http2Client(#[gc] GcStore<'root, u32>),
+}
+
+#[derive(GC)]
+struct Entry<'root, K, V> {
+    #[gc]
+    key: GcStore<'root, K>,
+    #[gc]
+    values: GcVec<'root, V>,
+}
+
+trait Schema {
+    type Row: Trace;
+}
+
+enum Ints {}
+
+impl Schema for Ints {
+    type Row = u32;
+}
+
+unsafe impl<'root> Reroot<'root> for Ints {
+    type Rerooted = Ints;
+}
+
+// `S` is never traced, only its rows are
+#[derive(GC)]
+#[gc(bound = "<S as Schema>::Row: Trace")]
+struct Table<'root, S: Schema> {
+    #[gc]
+    rows: GcVec<'root, <S as Schema>::Row>,
+}
+
+#[derive(GC)]
+struct Shared<'root> {
+    #[gc]
+    list: RefCell<Vec<GcStore<'root, u32>>>,
+    #[gc]
+    counted: Rc<GcStore<'root, u32>>,
+    #[gc]
+    boxed: Box<Option<GcStore<'root, u32>>>,
+    #[gc]
+    count: Cell<u32>,
+}
+
+/// The field names of the references of the only object of type `T`, by target type
+fn fields_of<T>(snapshot: &HeapSnapshot) -> Vec<(Option<&'static str>, &'static str)> {
+    let objects = snapshot.objects();
+    let idx = objects
+        .iter()
+        .position(|object| object.type_name == type_name::<T>())
+        .unwrap();
+    let mut fields: Vec<_> = snapshot
+        .fields(idx)
+        .iter()
+        .zip(snapshot.references(idx))
+        .map(|(field, &target)| (*field, objects[target].type_name))
+        .collect();
+    fields.sort_unstable();
+    fields
+}
+
+#[test]
+fn snapshot_field_names() {
+    letroot!(parent, pair);
+    parent.gc(Parent {
+        first: GcStore::new(1),
+        second: GcStore::new(2),
+    });
+    pair.gc(Pair(GcStore::new(3), GcStore::new(4)));
+
+    let snapshot = HeapSnapshot::capture();
+    assert_eq!(
+        fields_of::<Parent>(&snapshot),
+        [
+            (Some("first"), type_name::<u32>()),
+            (Some("second"), type_name::<u64>())
+        ]
+    );
+    // Tuple fields have no name to report
+    assert_eq!(
+        fields_of::<Pair>(&snapshot),
+        [(None, type_name::<u32>()), (None, type_name::<u64>())]
+    );
+
+    // The names label the edges of the DevTools export
+    let mut out = Vec::new();
+    snapshot.write_devtools(&mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let strings = json["strings"].as_array().unwrap();
+    assert!(strings.iter().any(|string| string == "first"));
+    assert!(strings.iter().any(|string| string == "second"));
+}
+
+#[test]
+fn struct_accessors() {
+    letroot!(tagged);
+    let tagged = tagged.gc(Tagged(GcStore::new(1), GcStore::new(2)));
+    nocturne::collect();
+
+    // Tuple fields are named by index, unless given a name
+    assert_eq!(*tagged.field_0(), 1);
+    assert_eq!(*tagged.second(), 2);
+}
+
+#[test]
+fn variant_accessors() {
+    letroot!(empty, point, line);
+    let empty: Gc<Shape> = empty.gc(Shape::Empty);
+    let point: Gc<Shape> = point.gc(Shape::Point(GcStore::new(1), 2));
+    let line: Gc<Shape> = line.gc(Shape::Line {
+        start: GcStore::new(3),
+        end: GcStore::new(4),
+    });
+    nocturne::collect();
+
+    assert_eq!(point.point_field_0().as_deref(), Some(&1));
+    assert_eq!(point.point_field_1().as_deref(), Some(&2));
+    assert_eq!(line.line_start().as_deref(), Some(&3));
+    assert_eq!(line.end().as_deref(), Some(&4));
+
+    // Accessors of another variant find nothing
+    for shape in [empty, line] {
+        assert!(shape.point_field_0().is_none());
+        assert!(shape.point_field_1().is_none());
+    }
+    for shape in [empty, point] {
+        assert!(shape.line_start().is_none());
+        assert!(shape.end().is_none());
+    }
+}
+
+#[test]
+fn variant_accessor_names() {
+    letroot!(server, client);
+    let server: Gc<Service> = server.gc(Service::HTTPServer(GcStore::new(1)));
+    let client: Gc<Service> = client.gc(Service::Http2Client(GcStore::new(2)));
+
+    // Runs of capitals are kept together as one word
+    assert_eq!(server.http_server_field_0().as_deref(), Some(&1));
+    assert_eq!(client.http2_client_field_0().as_deref(), Some(&2));
+}
+
+#[test]
+fn generic_types() {
+    {
+        letroot!(entry, table);
+        let entry = entry.gc(Entry {
+            key: GcStore::new(String::from("one")),
+            values: GcVec::<u32>::new(),
+        });
+        let table = table.gc(Table::<Ints> { rows: GcVec::new() });
+
+        // Elements pushed through the rerooted objects are kept alive by them
+        {
+            letroot!(inner_entry, inner_table, value, row);
+            inner_entry.reroot(entry).values().push(value.gc(1_u32));
+            inner_table.reroot(table).rows().push(row.gc(2_u32));
+        }
+        nocturne::collect();
+        assert_eq!(raw::count_managed_objects(), 5);
+
+        assert_eq!(*entry.key(), "one");
+        assert_eq!(*entry.values().borrow().get(0).unwrap(), 1);
+        assert_eq!(*table.rows().borrow().get(0).unwrap(), 2);
+    }
+
+    nocturne::collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
+
+#[test]
+fn shared_containers() {
+    {
+        letroot!(second);
+        let counted = Rc::new(GcStore::new(1_u32));
+        let second = second.gc(Shared {
+            list: RefCell::new(Vec::new()),
+            counted: counted.clone(),
+            boxed: Box::new(None),
+            count: Cell::new(0),
+        });
+
+        {
+            letroot!(first);
+            let first = first.gc(Shared {
+                list: RefCell::new(Vec::new()),
+                counted,
+                boxed: Box::new(Some(GcStore::new(2))),
+                count: Cell::new(0),
+            });
+
+            // Objects stored in a `RefCell` are managed by the next collection
+            first.list.borrow_mut().push(GcStore::new(3));
+            first.count().set(1);
+            nocturne::collect();
+            assert_eq!(raw::count_managed_objects(), 5);
+
+            assert_eq!(***first.counted(), 1);
+            assert_eq!(*first.boxed().unwrap(), 2);
+            assert_eq!(*first.list().borrow().get()[0], 3);
+            assert_eq!(first.count().get(), 1);
+        }
+
+        // The contents of an `Rc` live as long as one of the objects sharing it
+        nocturne::collect();
+        assert_eq!(raw::count_managed_objects(), 2);
+        assert_eq!(***second.counted(), 1);
+        assert!(second.boxed().is_none());
+        assert!(second.list().borrow().get().is_empty());
+    }
+
+    nocturne::collect();
+    assert_eq!(raw::count_managed_objects(), 0);
+}
@@ -0,0 +1,13 @@
+use nocturne::{GcStore, GC};
+
+trait Schema {
+    type Row;
+}
+
+#[derive(GC)]
+struct Table<'root, S: Schema> {
+    #[gc]
+    rows: GcStore<'root, S::Row>,
+}
+
+fn main() {}
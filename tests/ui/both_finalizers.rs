@@ -0,0 +1,10 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+#[gc(finalize, unsafe_finalize)]
+struct Foo<'root> {
+    #[gc]
+    item: GcStore<'root, i32>,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+enum Shape<'root> {
+    Circle {
+        #[gc(name = "center")]
+        center: GcStore<'root, i32>,
+    },
+    Square {
+        #[gc(name = "center")]
+        corner: GcStore<'root, i32>,
+    },
+}
+
+fn main() {}
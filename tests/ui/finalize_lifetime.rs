@@ -0,0 +1,11 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+#[gc(finalize)]
+struct Foo<'root, 'a> {
+    #[gc]
+    item: GcStore<'root, i32>,
+    name: &'a str,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+struct Pair<'root>(
+    #[gc] GcStore<'root, i32>,
+    #[gc(name = "field_0")] GcStore<'root, i32>,
+);
+
+#[derive(GC)]
+enum Shape<'root> {
+    Circle(#[gc(name = "square_field_0")] GcStore<'root, i32>),
+    Square(#[gc] GcStore<'root, i32>),
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+#[gc(bound = "T Trace")]
+struct Foo<'root, T> {
+    #[gc]
+    item: GcStore<'root, T>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+struct Foo<'root> {
+    #[gc = "item"]
+    item: GcStore<'root, i32>,
+}
+
+fn main() {}
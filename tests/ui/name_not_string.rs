@@ -0,0 +1,6 @@
+use nocturne::{GcStore, GC};
+
+#[derive(GC)]
+struct Foo<'root>(#[gc(name = 1)] GcStore<'root, i32>);
+
+fn main() {}